@@ -6,5 +6,5 @@ fn main() {
     let mut beanstalkd = Beanstalkd::localhost().unwrap();
     let (id, body) = beanstalkd.reserve().unwrap();
     println!("{}", body);
-    let _ = beanstalkd.release(id, 1024, 10).unwrap();
+    beanstalkd.release(id, 1024, 10).unwrap();
 }
@@ -6,5 +6,5 @@ fn main() {
     let mut beanstalkd = Beanstalkd::localhost().unwrap();
     let (id, body) = beanstalkd.reserve().unwrap();
     println!("{}", body);
-    let _ = beanstalkd.touch(id).unwrap();
+    beanstalkd.touch(id).unwrap();
 }
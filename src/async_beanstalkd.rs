@@ -0,0 +1,497 @@
+#[cfg(unix)]
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+use crate::commands::{self, Command};
+use crate::error::{BeanstalkdError, BeanstalkdResult};
+use crate::parse;
+use crate::protocol::{self, request_error, Decoder, Response, Status, PIPELINE_DEPTH};
+use crate::response::ReserveOutcome;
+use crate::stats::{JobStats, ServerStats, TubeStats};
+
+/// A client for tokio based applications. It offers the same commands as `Beanstalkd`, encoded
+/// and decoded by the same code, but never blocks the executor while waiting for the server.
+///
+/// Cancelling a call once its command was sent, e.g. with `tokio::time::timeout` around
+/// `reserve`, leaves the reply unread. Later calls then fail with a `ConnectionError` instead of
+/// taking that reply for their own, so connect again after cancelling a call.
+pub struct AsyncBeanstalkd<S: AsyncRead + AsyncWrite + Unpin = TcpStream> {
+    stream: BufStream<S>,
+    /// Set while a command waits for its reply and kept if the command failed in a way that
+    /// leaves the connection out of sync, or if the call was cancelled meanwhile
+    broken: bool,
+}
+
+impl AsyncBeanstalkd {
+    /// Connect to a running beanstalkd server
+    ///
+    /// Example: `let mut beanstalkd = AsyncBeanstalkd::connect("localhost", 11300).await.unwrap();`
+    pub async fn connect(host: &str, port: u16) -> BeanstalkdResult<AsyncBeanstalkd> {
+        let tcp_stream = TcpStream::connect((host, port))
+            .await
+            .map_err(BeanstalkdError::ConnectionError)?;
+
+        Ok(AsyncBeanstalkd::from_stream(tcp_stream))
+    }
+
+    /// Short hand method to connect to `localhost:11300`
+    pub async fn localhost() -> BeanstalkdResult<AsyncBeanstalkd> {
+        AsyncBeanstalkd::connect("localhost", 11300).await
+    }
+}
+
+#[cfg(unix)]
+impl AsyncBeanstalkd<UnixStream> {
+    /// Connect to a beanstalkd server listening on a Unix domain socket (`beanstalkd -l unix:path`)
+    pub async fn connect_unix<P: AsRef<Path>>(path: P)
+                                              -> BeanstalkdResult<AsyncBeanstalkd<UnixStream>> {
+        let unix_stream = UnixStream::connect(path)
+            .await
+            .map_err(BeanstalkdError::ConnectionError)?;
+
+        Ok(AsyncBeanstalkd::from_stream(unix_stream))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncBeanstalkd<S> {
+    /// Uses an already established connection to the server, e.g. a TLS stream or an in-memory
+    /// pipe in tests. The stream is buffered by the client.
+    pub fn from_stream(stream: S) -> AsyncBeanstalkd<S> {
+        AsyncBeanstalkd {
+            stream: BufStream::new(stream),
+            broken: false,
+        }
+    }
+
+    /// Change the tube where put new messages (Standard tube is called `default`)
+    pub async fn tube(&mut self, tube: &str) -> BeanstalkdResult<()> {
+        self.cmd(&commands::tube(tube)).await.map(|_| ())
+    }
+
+    /// Inserts a job into the client's currently used tube
+    pub async fn put(&mut self,
+                     body: &str,
+                     priority: u32,
+                     delay: u32,
+                     ttr: u32)
+                     -> BeanstalkdResult<u64> {
+        self.put_bytes(body.as_bytes(), priority, delay, ttr).await
+    }
+
+    /// Inserts a job with an arbitrary binary body into the client's currently used tube
+    pub async fn put_bytes(&mut self,
+                           body: &[u8],
+                           priority: u32,
+                           delay: u32,
+                           ttr: u32)
+                           -> BeanstalkdResult<u64> {
+        let message = commands::put(body, priority, delay, ttr);
+        self.cmd(&message).await.and_then(|response| parse::id(&message, response))
+    }
+
+    /// Inserts many jobs into the client's currently used tube. The commands are pipelined, so
+    /// the jobs don't wait for each other's round trip. Returns the id or the error of each job
+    /// in order. If the connection breaks, the ids received until then are still returned.
+    pub async fn put_many<I>(&mut self,
+                             bodies: I,
+                             priority: u32,
+                             delay: u32,
+                             ttr: u32)
+                             -> BeanstalkdResult<Vec<BeanstalkdResult<u64>>>
+        where I: IntoIterator,
+              I::Item: AsRef<[u8]>
+    {
+        let bodies: Vec<I::Item> = bodies.into_iter().collect();
+        let messages: Vec<Command> = bodies.iter()
+            .map(|body| commands::put(body.as_ref(), priority, delay, ttr))
+            .collect();
+        self.cmd_many(&messages).await.map(|responses| {
+            messages.iter()
+                .zip(responses)
+                .map(|(message, response)| response.and_then(|r| parse::id(message, r)))
+                .collect()
+        })
+    }
+
+    /// Get the next message out of the queue
+    pub async fn reserve(&mut self) -> BeanstalkdResult<(u64, String)> {
+        let message = commands::reserve();
+        self.cmd(&message)
+            .await
+            .and_then(|response| parse::job(&message, response))
+            .and_then(|job| parse::utf8_job(&message, job))
+    }
+
+    /// Get the next message out of the queue as raw bytes
+    pub async fn reserve_bytes(&mut self) -> BeanstalkdResult<(u64, Vec<u8>)> {
+        let message = commands::reserve();
+        self.cmd(&message).await.and_then(|response| parse::job(&message, response))
+    }
+
+    /// Get the next message out of the queue with timeout. If the timeout runs out a None is returned
+    /// in BeanstalkdResult.
+    pub async fn reserve_with_timeout(&mut self,
+                                      timeout: u64)
+                                      -> BeanstalkdResult<Option<(u64, String)>> {
+        let message = commands::reserve_with_timeout(timeout);
+        self.cmd(&message)
+            .await
+            .and_then(|response| parse::optional_job(&message, response))
+            .and_then(|job| job.map(|job| parse::utf8_job(&message, job)).transpose())
+    }
+
+    /// Get the next message out of the queue with timeout as raw bytes. If the timeout runs out a
+    /// None is returned in BeanstalkdResult.
+    pub async fn reserve_with_timeout_bytes(&mut self,
+                                            timeout: u64)
+                                            -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        let message = commands::reserve_with_timeout(timeout);
+        self.cmd(&message).await.and_then(|response| parse::optional_job(&message, response))
+    }
+
+    /// Get the next message out of the queue. Unlike `reserve` this reports a pending deadline of
+    /// an already reserved job as `ReserveOutcome::DeadlineSoon` instead of an error.
+    pub async fn reserve_outcome(&mut self) -> BeanstalkdResult<ReserveOutcome> {
+        let message = commands::reserve();
+        parse::reserve_outcome(&message, self.cmd(&message).await)
+            .and_then(|outcome| parse::utf8_outcome(&message, outcome))
+    }
+
+    /// Same as `reserve_outcome` but returns the job body as raw bytes
+    pub async fn reserve_outcome_bytes(&mut self) -> BeanstalkdResult<ReserveOutcome<Vec<u8>>> {
+        let message = commands::reserve();
+        parse::reserve_outcome(&message, self.cmd(&message).await)
+    }
+
+    /// Get the next message out of the queue with timeout. Unlike `reserve_with_timeout` this
+    /// reports a pending deadline of an already reserved job as `ReserveOutcome::DeadlineSoon`
+    /// instead of an error.
+    pub async fn reserve_outcome_with_timeout(&mut self,
+                                              timeout: u64)
+                                              -> BeanstalkdResult<ReserveOutcome> {
+        let message = commands::reserve_with_timeout(timeout);
+        parse::reserve_outcome(&message, self.cmd(&message).await)
+            .and_then(|outcome| parse::utf8_outcome(&message, outcome))
+    }
+
+    /// Same as `reserve_outcome_with_timeout` but returns the job body as raw bytes
+    pub async fn reserve_outcome_with_timeout_bytes(&mut self,
+                                                    timeout: u64)
+                                                    -> BeanstalkdResult<ReserveOutcome<Vec<u8>>> {
+        let message = commands::reserve_with_timeout(timeout);
+        parse::reserve_outcome(&message, self.cmd(&message).await)
+    }
+
+    /// Reserves the job with the given id regardless of its state or tube. If the job doesn't
+    /// exist or is already reserved a None is returned in BeanstalkdResult.
+    pub async fn reserve_job(&mut self, id: u64) -> BeanstalkdResult<Option<(u64, String)>> {
+        let message = commands::reserve_job(id);
+        self.cmd(&message)
+            .await
+            .and_then(|response| parse::optional_job(&message, response))
+            .and_then(|job| job.map(|job| parse::utf8_job(&message, job)).transpose())
+    }
+
+    /// Same as `reserve_job` but returns the job body as raw bytes
+    pub async fn reserve_job_bytes(&mut self, id: u64) -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        let message = commands::reserve_job(id);
+        self.cmd(&message).await.and_then(|response| parse::optional_job(&message, response))
+    }
+
+    /// Deletes a message out of the queue. Returns `false` if the job doesn't exist or is
+    /// reserved by another client.
+    pub async fn delete(&mut self, id: u64) -> BeanstalkdResult<bool> {
+        self.cmd(&commands::delete(id)).await.map(|r| r.status == Status::DELETED)
+    }
+
+    /// Deletes many messages out of the queue with pipelined commands. Returns the result of
+    /// each deletion in order, which is `false` if the job doesn't exist or is reserved by
+    /// another client.
+    pub async fn delete_many(&mut self,
+                             ids: &[u64])
+                             -> BeanstalkdResult<Vec<BeanstalkdResult<bool>>> {
+        let messages: Vec<Command> = ids.iter().map(|&id| commands::delete(id)).collect();
+        self.cmd_many(&messages).await.map(|responses| {
+            responses.into_iter()
+                .map(|response| response.map(|r| r.status == Status::DELETED))
+                .collect()
+        })
+    }
+
+    /// Release a job in the queue. Returns `false` if the job isn't reserved by this client or
+    /// the server, short of memory, buried it instead.
+    pub async fn release(&mut self, id: u64, priority: u32, delay: u32) -> BeanstalkdResult<bool> {
+        let message = commands::release(id, priority, delay);
+        self.cmd(&message).await.map(|r| r.status == Status::RELEASED)
+    }
+
+    /// Bury a job in the queue. Returns `false` if the job isn't reserved by this client.
+    pub async fn bury(&mut self, id: u64, priority: u32) -> BeanstalkdResult<bool> {
+        self.cmd(&commands::bury(id, priority)).await.map(|r| r.status == Status::BURIED)
+    }
+
+    /// Touch a job in the queue. Returns `false` if the job isn't reserved by this client.
+    pub async fn touch(&mut self, id: u64) -> BeanstalkdResult<bool> {
+        self.cmd(&commands::touch(id)).await.map(|r| r.status == Status::TOUCHED)
+    }
+
+    /// Moves up to `bound` buried (or, if there are none, delayed) jobs of the currently used
+    /// tube into the ready queue. Returns the number of jobs actually kicked.
+    pub async fn kick(&mut self, bound: u64) -> BeanstalkdResult<u64> {
+        let message = commands::kick(bound);
+        self.cmd(&message).await.and_then(|response| parse::id(&message, response))
+    }
+
+    /// Kicks a single buried or delayed job into the ready queue. Returns `false` if the job
+    /// doesn't exist or is not in a kickable state.
+    pub async fn kick_job(&mut self, id: u64) -> BeanstalkdResult<bool> {
+        self.cmd(&commands::kick_job(id)).await.map(|r| r.status == Status::KICKED)
+    }
+
+    /// Returns all available stats
+    pub async fn stats(&mut self) -> BeanstalkdResult<ServerStats> {
+        let message = commands::stats();
+        self.cmd(&message).await.and_then(|r| ServerStats::from_map(parse::hashmap(r)?, &message))
+    }
+
+    /// Returns stats for the specified job or `None` if the job doesn't exist
+    pub async fn stats_job(&mut self, id: u64) -> BeanstalkdResult<Option<JobStats>> {
+        let message = commands::stats_job(id);
+        let response = self.cmd(&message).await?;
+        if response.status == Status::NOT_FOUND {
+            Ok(None)
+        } else {
+            JobStats::from_map(parse::hashmap(response)?, &message).map(Some)
+        }
+    }
+
+    /// Returns stats for the specified tube or `None` if the tube doesn't exist
+    pub async fn stats_tube(&mut self, tube: &str) -> BeanstalkdResult<Option<TubeStats>> {
+        let message = commands::stats_tube(tube);
+        let response = self.cmd(&message).await?;
+        if response.status == Status::NOT_FOUND {
+            Ok(None)
+        } else {
+            TubeStats::from_map(parse::hashmap(response)?, &message).map(Some)
+        }
+    }
+
+    /// Add new tube to watch list
+    pub async fn watch(&mut self, tube: &str) -> BeanstalkdResult<u64> {
+        let message = commands::watch(tube);
+        self.cmd(&message).await.and_then(|response| parse::id(&message, response))
+    }
+
+    /// Removes the named tube from the watch list for the current connection
+    pub async fn ignore(&mut self, tube: &str) -> BeanstalkdResult<Option<u64>> {
+        let message = commands::ignore(tube);
+        self.cmd(&message).await.and_then(|response| parse::count(&message, response))
+    }
+
+    /// Returns the names of all existing tubes
+    pub async fn list_tubes(&mut self) -> BeanstalkdResult<Vec<String>> {
+        self.cmd(&commands::list_tubes()).await.and_then(parse::list)
+    }
+
+    /// Returns the name of the tube currently being used
+    pub async fn list_tube_used(&mut self) -> BeanstalkdResult<String> {
+        let message = commands::list_tube_used();
+        self.cmd(&message).await.and_then(|response| parse::name(&message, response))
+    }
+
+    /// Returns the names of the tubes currently being watched
+    pub async fn list_tubes_watched(&mut self) -> BeanstalkdResult<Vec<String>> {
+        self.cmd(&commands::list_tubes_watched()).await.and_then(parse::list)
+    }
+
+    /// Delays any new job being reserved from the tube for the given time. Partial seconds are
+    /// rounded up. Returns `false` if the tube doesn't exist. Delays longer than the server
+    /// supports fail with `BeanstalkdError::BadFormat`.
+    pub async fn pause_tube(&mut self, tube: &str, delay: Duration) -> BeanstalkdResult<bool> {
+        self.cmd(&commands::pause_tube(tube, delay)).await.map(|r| r.status == Status::PAUSED)
+    }
+
+    /// Lifts a pause set by `pause_tube` so jobs can be reserved from the tube again. Returns
+    /// `false` if the tube doesn't exist.
+    pub async fn resume_tube(&mut self, tube: &str) -> BeanstalkdResult<bool> {
+        self.pause_tube(tube, Duration::from_secs(0)).await
+    }
+
+    /// Peeks the job with the given id
+    pub async fn peek(&mut self, id: u64) -> BeanstalkdResult<Option<(u64, String)>> {
+        self.peek_utf8(&commands::peek(id)).await
+    }
+
+    /// Peeks the job with the given id and returns its body as raw bytes
+    pub async fn peek_bytes(&mut self, id: u64) -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        self.peek_cmd(&commands::peek(id)).await
+    }
+
+    /// Peeks the next ready job
+    pub async fn peek_ready(&mut self) -> BeanstalkdResult<Option<(u64, String)>> {
+        self.peek_utf8(&commands::peek_ready()).await
+    }
+
+    /// Peeks the next ready job and returns its body as raw bytes
+    pub async fn peek_ready_bytes(&mut self) -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        self.peek_cmd(&commands::peek_ready()).await
+    }
+
+    /// Peeks the next delayed job
+    pub async fn peek_delayed(&mut self) -> BeanstalkdResult<Option<(u64, String)>> {
+        self.peek_utf8(&commands::peek_delayed()).await
+    }
+
+    /// Peeks the next delayed job and returns its body as raw bytes
+    pub async fn peek_delayed_bytes(&mut self) -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        self.peek_cmd(&commands::peek_delayed()).await
+    }
+
+    /// Peeks the next buried job
+    pub async fn peek_buried(&mut self) -> BeanstalkdResult<Option<(u64, String)>> {
+        self.peek_utf8(&commands::peek_buried()).await
+    }
+
+    /// Peeks the next buried job and returns its body as raw bytes
+    pub async fn peek_buried_bytes(&mut self) -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        self.peek_cmd(&commands::peek_buried()).await
+    }
+
+    /// Delete all the jobs in the ready state
+    pub async fn delete_all_ready(&mut self) -> BeanstalkdResult<()> {
+        self.delete_all_cmd(commands::peek_ready).await
+    }
+
+    /// Delete all the jobs in the delayed state
+    pub async fn delete_all_delayed(&mut self) -> BeanstalkdResult<()> {
+        self.delete_all_cmd(commands::peek_delayed).await
+    }
+
+    /// Delete all the jobs in the buried state
+    pub async fn delete_all_buried(&mut self) -> BeanstalkdResult<()> {
+        self.delete_all_cmd(commands::peek_buried).await
+    }
+
+    /// Delete all jobs (in any state)
+    pub async fn delete_all(&mut self) -> BeanstalkdResult<()> {
+        self.delete_all_ready().await?;
+        self.delete_all_delayed().await?;
+        self.delete_all_buried().await?;
+        Ok(())
+    }
+
+    async fn delete_all_cmd(&mut self, peek: fn() -> Command<'static>) -> BeanstalkdResult<()> {
+        loop {
+            match self.peek_cmd(&peek()).await? {
+                Some((job_id, _)) => {
+                    self.delete(job_id).await?;
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Returns:
+    /// - Ok(Some(_)) if a job is found
+    /// - Ok(None) if no job found
+    /// - Err(_) if an error occurred
+    async fn peek_cmd(&mut self,
+                      message: &Command<'_>)
+                      -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        self.cmd(message).await.and_then(|response| parse::optional_job(message, response))
+    }
+
+    /// Same as `peek_cmd` but decodes the job body as UTF-8
+    async fn peek_utf8(&mut self,
+                       message: &Command<'_>)
+                       -> BeanstalkdResult<Option<(u64, String)>> {
+        self.peek_cmd(message)
+            .await
+            .and_then(|job| job.map(|job| parse::utf8_job(message, job)).transpose())
+    }
+
+    async fn cmd(&mut self, message: &Command<'_>) -> BeanstalkdResult<Response> {
+        if self.broken {
+            return Err(protocol::connection_broken());
+        }
+        self.broken = true;
+        let response = self.send(message).await;
+        self.broken = matches!(&response, Err(e) if !protocol::is_server_error(e));
+        response
+    }
+
+    async fn send(&mut self, message: &Command<'_>) -> BeanstalkdResult<Response> {
+        self.write(message).await?;
+        self.stream.flush().await.map_err(|e| request_error(message, e))?;
+
+        self.receive(message).await
+    }
+
+    /// Sends the commands pipelined. If the connection breaks the replies received until then are
+    /// kept and every unanswered command gets an error.
+    async fn cmd_many(&mut self,
+                      messages: &[Command<'_>])
+                      -> BeanstalkdResult<Vec<BeanstalkdResult<Response>>> {
+        let mut responses = Vec::with_capacity(messages.len());
+        if let Err(e) = self.send_many(messages, &mut responses).await {
+            protocol::fail_unanswered(messages, &mut responses, e, messages.len());
+        }
+        Ok(responses)
+    }
+
+    /// Sends the commands in batches of `PIPELINE_DEPTH`, appending their replies to `responses`
+    async fn send_many(&mut self,
+                       messages: &[Command<'_>],
+                       responses: &mut Vec<BeanstalkdResult<Response>>)
+                       -> BeanstalkdResult<()> {
+        if self.broken {
+            return Err(protocol::connection_broken());
+        }
+        // Error replies of the server are kept in `responses`, any error returned breaks the
+        // connection
+        self.broken = true;
+        for batch in messages.chunks(PIPELINE_DEPTH) {
+            for message in batch {
+                self.write(message).await?;
+            }
+            if let Some(message) = batch.last() {
+                self.stream.flush().await.map_err(|e| request_error(message, e))?;
+            }
+            for message in batch {
+                match self.receive(message).await {
+                    Err(e) if !protocol::is_server_error(&e) => return Err(e),
+                    response => responses.push(response),
+                }
+            }
+        }
+        self.broken = false;
+        Ok(())
+    }
+
+    async fn write(&mut self, message: &Command<'_>) -> BeanstalkdResult<()> {
+        for chunk in message.chunks().iter() {
+            self.stream.write_all(chunk).await.map_err(|e| request_error(message, e))?;
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self, message: &Command<'_>) -> BeanstalkdResult<Response> {
+        let mut decoder = Decoder::new();
+        loop {
+            let input = self.stream.fill_buf().await.map_err(|e| request_error(message, e))?;
+            // Zero bytes read indicates the TCP connection was closed.
+            if input.is_empty() {
+                return Err(protocol::connection_closed());
+            }
+            let (used, response) = decoder.decode(message.line(), input);
+            self.stream.consume(used);
+            if let Some(response) = response {
+                return response;
+            }
+        }
+    }
+}
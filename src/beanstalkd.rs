@@ -1,21 +1,52 @@
-extern crate bufstream;
-
-use std::collections::HashMap;
+use std::io::{self, Read, Write};
 use std::net::TcpStream;
-use self::bufstream::BufStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use bufstream::BufStream;
+
+use crate::commands::{self, Command};
+use crate::error::{BeanstalkdError, BeanstalkdResult};
+use crate::options::ReconnectPolicy;
+use crate::parse;
+use crate::protocol::{self, PIPELINE_DEPTH};
+use crate::request::Request;
+use crate::response::{ReserveOutcome, Response, Status};
+use crate::stats::{JobStats, ServerStats, TubeStats};
+
+/// A client connected to a beanstalkd server over any kind of stream, a TCP connection by default
+pub struct Beanstalkd<S: Read + Write = TcpStream> {
+    stream: BufStream<S>,
+    timeouts: Option<Timeouts<S>>,
+    session: Session,
+    reconnect: Option<Reconnect<S>>,
+    /// Set once a command failed in a way that leaves the connection out of sync, e.g. by timing
+    /// out before its reply arrived or with a malformed reply whose rest is still unread
+    broken: bool,
+}
 
-use commands;
-use error::{BeanstalkdError, BeanstalkdResult};
-use parse;
-use request::Request;
-use response::{Response, Status};
+const DEFAULT_TUBE: &str = "default";
 
-macro_rules! try {
-    ($e:expr) => (match $e { Ok(e) => e, Err(_) => return Err(BeanstalkdError::ConnectionError) })
+/// The tubes selected on the connection, which have to be selected again after reconnecting
+struct Session {
+    used: String,
+    watched: Vec<String>,
 }
 
-pub struct Beanstalkd {
-    stream: BufStream<TcpStream>,
+/// How to open a new connection once the current one is lost
+pub(crate) struct Reconnect<S> {
+    pub policy: ReconnectPolicy,
+    pub connect: Box<dyn FnMut() -> BeanstalkdResult<S> + Send + Sync>,
+}
+
+/// The I/O timeout of a connection, which reserve commands extend by the time the server may
+/// wait for a job. `set_read_timeout` changes the timeout on the stream.
+pub(crate) struct Timeouts<S> {
+    pub io: Duration,
+    pub set_read_timeout: fn(&S, Option<Duration>) -> io::Result<()>,
 }
 
 impl Beanstalkd {
@@ -23,19 +54,57 @@ impl Beanstalkd {
     ///
     /// Example: `let mut beanstalkd = Beanstalkd::connect('localhost', 11300).unwrap();`
     pub fn connect(host: &str, port: u16) -> BeanstalkdResult<Beanstalkd> {
-        let tcp_stream = try!(TcpStream::connect(&(host, port)));
+        let tcp_stream = TcpStream::connect((host, port))
+            .map_err(BeanstalkdError::ConnectionError)?;
 
-        Ok(Beanstalkd { stream: BufStream::new(tcp_stream) })
+        Ok(Beanstalkd::from_stream(tcp_stream))
     }
 
     /// Short hand method to connect to `localhost:11300`
     pub fn localhost() -> BeanstalkdResult<Beanstalkd> {
         Beanstalkd::connect("localhost", 11300)
     }
+}
+
+#[cfg(unix)]
+impl Beanstalkd<UnixStream> {
+    /// Connect to a beanstalkd server listening on a Unix domain socket (`beanstalkd -l unix:path`)
+    pub fn connect_unix<P: AsRef<Path>>(path: P) -> BeanstalkdResult<Beanstalkd<UnixStream>> {
+        let unix_stream = UnixStream::connect(path).map_err(BeanstalkdError::ConnectionError)?;
+
+        Ok(Beanstalkd::from_stream(unix_stream))
+    }
+}
+
+impl<S: Read + Write> Beanstalkd<S> {
+    /// Uses an already established connection to the server, e.g. a TLS stream or an in-memory
+    /// pipe in tests. The stream is buffered by the client.
+    pub fn from_stream(stream: S) -> Beanstalkd<S> {
+        Beanstalkd::with_timeouts(stream, None)
+    }
+
+    pub(crate) fn with_timeouts(stream: S, timeouts: Option<Timeouts<S>>) -> Beanstalkd<S> {
+        Beanstalkd {
+            stream: BufStream::new(stream),
+            timeouts,
+            session: Session {
+                used: DEFAULT_TUBE.to_string(),
+                watched: vec![DEFAULT_TUBE.to_string()],
+            },
+            reconnect: None,
+            broken: false,
+        }
+    }
+
+    pub(crate) fn set_reconnect(&mut self, reconnect: Reconnect<S>) {
+        self.reconnect = Some(reconnect);
+    }
 
     /// Change the tube where put new messages (Standard tube is called `default`)
     pub fn tube(&mut self, tube: &str) -> BeanstalkdResult<()> {
-        self.cmd(commands::tube(tube)).map(|_| ())
+        self.cmd(&commands::tube(tube))?;
+        self.session.used = tube.to_string();
+        Ok(())
     }
 
     /// Inserts a job into the client's currently used tube
@@ -45,95 +114,305 @@ impl Beanstalkd {
                delay: u32,
                ttr: u32)
                -> BeanstalkdResult<u64> {
-        self.cmd(commands::put(body, priority, delay, ttr)).map(parse::id)
+        self.put_bytes(body.as_bytes(), priority, delay, ttr)
+    }
+
+    /// Inserts a job with an arbitrary binary body into the client's currently used tube
+    pub fn put_bytes(&mut self,
+                     body: &[u8],
+                     priority: u32,
+                     delay: u32,
+                     ttr: u32)
+                     -> BeanstalkdResult<u64> {
+        let message = commands::put(body, priority, delay, ttr);
+        self.cmd(&message).and_then(|response| parse::id(&message, response))
+    }
+
+    /// Inserts many jobs into the client's currently used tube. The commands are pipelined, so
+    /// the jobs don't wait for each other's round trip. Returns the id or the error of each job
+    /// in order. If the connection is lost the ids received until then are still returned. With
+    /// a reconnect policy the jobs the server never got are put on the new connection.
+    pub fn put_many<I>(&mut self,
+                       bodies: I,
+                       priority: u32,
+                       delay: u32,
+                       ttr: u32)
+                       -> BeanstalkdResult<Vec<BeanstalkdResult<u64>>>
+        where I: IntoIterator,
+              I::Item: AsRef<[u8]>
+    {
+        let bodies: Vec<I::Item> = bodies.into_iter().collect();
+        let messages: Vec<Command> = bodies.iter()
+            .map(|body| commands::put(body.as_ref(), priority, delay, ttr))
+            .collect();
+        self.cmd_many(&messages).map(|responses| {
+            messages.iter()
+                .zip(responses)
+                .map(|(message, response)| response.and_then(|r| parse::id(message, r)))
+                .collect()
+        })
     }
 
     /// Get the next message out of the queue
     pub fn reserve(&mut self) -> BeanstalkdResult<(u64, String)> {
-        self.cmd(commands::reserve()).map(|r| (parse::id(r.clone()), parse::body(r)))
+        let message = commands::reserve();
+        self.reserve_cmd(&message, None)
+            .and_then(|response| parse::job(&message, response))
+            .and_then(|job| parse::utf8_job(&message, job))
+    }
+
+    /// Get the next message out of the queue as raw bytes
+    pub fn reserve_bytes(&mut self) -> BeanstalkdResult<(u64, Vec<u8>)> {
+        let message = commands::reserve();
+        self.reserve_cmd(&message, None).and_then(|response| parse::job(&message, response))
     }
 
     /// Get the next message out of the queue with timeout. If the timeout runs out a None is returned
     /// in BeanstalkdResult.
     pub fn reserve_with_timeout(&mut self, timeout: u64) -> BeanstalkdResult<Option<(u64, String)>> {
-        self.cmd(commands::reserve_with_timeout(timeout))
-            .map(|r| {
-                if r.status == Status::TIMED_OUT {
-                    None
-                } else {
-                    Some((parse::id(r.clone()), parse::body(r)))
-                }
-            })
-    } 
+        let message = commands::reserve_with_timeout(timeout);
+        self.reserve_cmd(&message, Some(Duration::from_secs(timeout)))
+            .and_then(|response| parse::optional_job(&message, response))
+            .and_then(|job| job.map(|job| parse::utf8_job(&message, job)).transpose())
+    }
 
-    /// Deletes a message out of the queue
-    pub fn delete(&mut self, id: u64) -> BeanstalkdResult<()> {
-        self.cmd(commands::delete(id)).map(|_| ())
+    /// Get the next message out of the queue with timeout as raw bytes. If the timeout runs out a
+    /// None is returned in BeanstalkdResult.
+    pub fn reserve_with_timeout_bytes(&mut self,
+                                      timeout: u64)
+                                      -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        let message = commands::reserve_with_timeout(timeout);
+        self.reserve_cmd(&message, Some(Duration::from_secs(timeout)))
+            .and_then(|response| parse::optional_job(&message, response))
     }
 
-    /// Release a job in the queue
-    pub fn release(&mut self, id: u64, priority: u32, delay: u32) -> BeanstalkdResult<()> {
-        self.cmd(commands::release(id, priority, delay)).map(|_| ())
+    /// Get the next message out of the queue. Unlike `reserve` this reports a pending deadline of
+    /// an already reserved job as `ReserveOutcome::DeadlineSoon` instead of an error.
+    pub fn reserve_outcome(&mut self) -> BeanstalkdResult<ReserveOutcome> {
+        let message = commands::reserve();
+        parse::reserve_outcome(&message, self.reserve_cmd(&message, None))
+            .and_then(|outcome| parse::utf8_outcome(&message, outcome))
     }
 
-    /// Bury a job in the queue
-    pub fn bury(&mut self, id: u64, priority: u32) -> BeanstalkdResult<()> {
-        self.cmd(commands::bury(id, priority)).map(|_| ())
+    /// Same as `reserve_outcome` but returns the job body as raw bytes
+    pub fn reserve_outcome_bytes(&mut self) -> BeanstalkdResult<ReserveOutcome<Vec<u8>>> {
+        let message = commands::reserve();
+        parse::reserve_outcome(&message, self.reserve_cmd(&message, None))
     }
 
-    /// Touch a job in the queue
-    pub fn touch(&mut self, id: u64) -> BeanstalkdResult<()> {
-        self.cmd(commands::touch(id)).map(|_| ())
+    /// Get the next message out of the queue with timeout. Unlike `reserve_with_timeout` this
+    /// reports a pending deadline of an already reserved job as `ReserveOutcome::DeadlineSoon`
+    /// instead of an error.
+    pub fn reserve_outcome_with_timeout(&mut self,
+                                        timeout: u64)
+                                        -> BeanstalkdResult<ReserveOutcome> {
+        let message = commands::reserve_with_timeout(timeout);
+        let response = self.reserve_cmd(&message, Some(Duration::from_secs(timeout)));
+        parse::reserve_outcome(&message, response)
+            .and_then(|outcome| parse::utf8_outcome(&message, outcome))
+    }
+
+    /// Same as `reserve_outcome_with_timeout` but returns the job body as raw bytes
+    pub fn reserve_outcome_with_timeout_bytes(&mut self,
+                                              timeout: u64)
+                                              -> BeanstalkdResult<ReserveOutcome<Vec<u8>>> {
+        let message = commands::reserve_with_timeout(timeout);
+        let response = self.reserve_cmd(&message, Some(Duration::from_secs(timeout)));
+        parse::reserve_outcome(&message, response)
+    }
+
+    /// Reserves the job with the given id regardless of its state or tube. If the job doesn't
+    /// exist or is already reserved a None is returned in BeanstalkdResult.
+    pub fn reserve_job(&mut self, id: u64) -> BeanstalkdResult<Option<(u64, String)>> {
+        let message = commands::reserve_job(id);
+        self.cmd(&message)
+            .and_then(|response| parse::optional_job(&message, response))
+            .and_then(|job| job.map(|job| parse::utf8_job(&message, job)).transpose())
+    }
+
+    /// Same as `reserve_job` but returns the job body as raw bytes
+    pub fn reserve_job_bytes(&mut self, id: u64) -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        let message = commands::reserve_job(id);
+        self.cmd(&message).and_then(|response| parse::optional_job(&message, response))
+    }
+
+    /// Deletes a message out of the queue. Returns `false` if the job doesn't exist or is
+    /// reserved by another client.
+    pub fn delete(&mut self, id: u64) -> BeanstalkdResult<bool> {
+        self.cmd(&commands::delete(id)).map(|r| r.status == Status::DELETED)
+    }
+
+    /// Deletes many messages out of the queue with pipelined commands. Returns the result of
+    /// each deletion in order, which is `false` if the job doesn't exist or is reserved by
+    /// another client.
+    pub fn delete_many(&mut self, ids: &[u64]) -> BeanstalkdResult<Vec<BeanstalkdResult<bool>>> {
+        let messages: Vec<Command> = ids.iter().map(|&id| commands::delete(id)).collect();
+        self.cmd_many(&messages).map(|responses| {
+            responses.into_iter()
+                .map(|response| response.map(|r| r.status == Status::DELETED))
+                .collect()
+        })
+    }
+
+    /// Release a job in the queue. Returns `false` if the job isn't reserved by this client or
+    /// the server, short of memory, buried it instead.
+    pub fn release(&mut self, id: u64, priority: u32, delay: u32) -> BeanstalkdResult<bool> {
+        self.cmd(&commands::release(id, priority, delay)).map(|r| r.status == Status::RELEASED)
+    }
+
+    /// Bury a job in the queue. Returns `false` if the job isn't reserved by this client.
+    pub fn bury(&mut self, id: u64, priority: u32) -> BeanstalkdResult<bool> {
+        self.cmd(&commands::bury(id, priority)).map(|r| r.status == Status::BURIED)
+    }
+
+    /// Touch a job in the queue. Returns `false` if the job isn't reserved by this client.
+    pub fn touch(&mut self, id: u64) -> BeanstalkdResult<bool> {
+        self.cmd(&commands::touch(id)).map(|r| r.status == Status::TOUCHED)
+    }
+
+    /// Moves up to `bound` buried (or, if there are none, delayed) jobs of the currently used
+    /// tube into the ready queue. Returns the number of jobs actually kicked.
+    pub fn kick(&mut self, bound: u64) -> BeanstalkdResult<u64> {
+        let message = commands::kick(bound);
+        self.cmd(&message).and_then(|response| parse::id(&message, response))
+    }
+
+    /// Kicks a single buried or delayed job into the ready queue. Returns `false` if the job
+    /// doesn't exist or is not in a kickable state.
+    pub fn kick_job(&mut self, id: u64) -> BeanstalkdResult<bool> {
+        self.cmd(&commands::kick_job(id)).map(|r| r.status == Status::KICKED)
     }
 
     /// Returns all available stats
-    pub fn stats(&mut self) -> BeanstalkdResult<HashMap<String, String>> {
-        self.cmd(commands::stats()).map(parse::hashmap)
+    pub fn stats(&mut self) -> BeanstalkdResult<ServerStats> {
+        let message = commands::stats();
+        self.cmd(&message).and_then(|r| ServerStats::from_map(parse::hashmap(r)?, &message))
+    }
+
+    /// Returns stats for the specified job or `None` if the job doesn't exist
+    pub fn stats_job(&mut self, id: u64) -> BeanstalkdResult<Option<JobStats>> {
+        let message = commands::stats_job(id);
+        let response = self.cmd(&message)?;
+        if response.status == Status::NOT_FOUND {
+            Ok(None)
+        } else {
+            JobStats::from_map(parse::hashmap(response)?, &message).map(Some)
+        }
     }
 
-    /// Returns stats for the specified job
-    pub fn stats_job(&mut self, id: u64) -> BeanstalkdResult<HashMap<String, String>> {
-        self.cmd(commands::stats_job(id)).map(parse::hashmap)
+    /// Returns stats for the specified tube or `None` if the tube doesn't exist
+    pub fn stats_tube(&mut self, tube: &str) -> BeanstalkdResult<Option<TubeStats>> {
+        let message = commands::stats_tube(tube);
+        let response = self.cmd(&message)?;
+        if response.status == Status::NOT_FOUND {
+            Ok(None)
+        } else {
+            TubeStats::from_map(parse::hashmap(response)?, &message).map(Some)
+        }
     }
 
     /// Add new tube to watch list
     pub fn watch(&mut self, tube: &str) -> BeanstalkdResult<u64> {
-        self.cmd(commands::watch(tube)).map(parse::id)
+        let message = commands::watch(tube);
+        let count = self.cmd(&message).and_then(|response| parse::id(&message, response))?;
+        if !self.session.watched.iter().any(|watched| watched == tube) {
+            self.session.watched.push(tube.to_string());
+        }
+        Ok(count)
     }
 
     /// Removes the named tube from the watch list for the current connection
     pub fn ignore(&mut self, tube: &str) -> BeanstalkdResult<Option<u64>> {
-        self.cmd(commands::ignore(tube)).map(parse::count)
+        let message = commands::ignore(tube);
+        let count = self.cmd(&message).and_then(|response| parse::count(&message, response))?;
+        if count.is_some() {
+            self.session.watched.retain(|watched| watched != tube);
+        }
+        Ok(count)
+    }
+
+    /// Returns the names of all existing tubes
+    pub fn list_tubes(&mut self) -> BeanstalkdResult<Vec<String>> {
+        self.cmd(&commands::list_tubes()).and_then(parse::list)
+    }
+
+    /// Returns the name of the tube currently being used
+    pub fn list_tube_used(&mut self) -> BeanstalkdResult<String> {
+        let message = commands::list_tube_used();
+        self.cmd(&message).and_then(|response| parse::name(&message, response))
+    }
+
+    /// Returns the names of the tubes currently being watched
+    pub fn list_tubes_watched(&mut self) -> BeanstalkdResult<Vec<String>> {
+        self.cmd(&commands::list_tubes_watched()).and_then(parse::list)
+    }
+
+    /// Delays any new job being reserved from the tube for the given time. Partial seconds are
+    /// rounded up. Returns `false` if the tube doesn't exist. Delays longer than the server
+    /// supports fail with `BeanstalkdError::BadFormat`.
+    pub fn pause_tube(&mut self, tube: &str, delay: Duration) -> BeanstalkdResult<bool> {
+        self.cmd(&commands::pause_tube(tube, delay)).map(|r| r.status == Status::PAUSED)
+    }
+
+    /// Lifts a pause set by `pause_tube` so jobs can be reserved from the tube again. Returns
+    /// `false` if the tube doesn't exist.
+    pub fn resume_tube(&mut self, tube: &str) -> BeanstalkdResult<bool> {
+        self.pause_tube(tube, Duration::from_secs(0))
+    }
+
+    /// Peeks the job with the given id
+    pub fn peek(&mut self, id: u64) -> BeanstalkdResult<Option<(u64, String)>> {
+        self.peek_utf8(&commands::peek(id))
+    }
+
+    /// Peeks the job with the given id and returns its body as raw bytes
+    pub fn peek_bytes(&mut self, id: u64) -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        self.peek_cmd(&commands::peek(id))
     }
 
     /// Peeks the next ready job
     pub fn peek_ready(&mut self) -> BeanstalkdResult<Option<(u64, String)>> {
-        self.peek_cmd(commands::peek_ready())
+        self.peek_utf8(&commands::peek_ready())
+    }
+
+    /// Peeks the next ready job and returns its body as raw bytes
+    pub fn peek_ready_bytes(&mut self) -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        self.peek_cmd(&commands::peek_ready())
     }
 
     /// Peeks the next delayed job
     pub fn peek_delayed(&mut self) -> BeanstalkdResult<Option<(u64, String)>> {
-        self.peek_cmd(commands::peek_delayed())
+        self.peek_utf8(&commands::peek_delayed())
+    }
+
+    /// Peeks the next delayed job and returns its body as raw bytes
+    pub fn peek_delayed_bytes(&mut self) -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        self.peek_cmd(&commands::peek_delayed())
     }
 
     /// Peeks the next buried job
     pub fn peek_buried(&mut self) -> BeanstalkdResult<Option<(u64, String)>> {
-        self.peek_cmd(commands::peek_buried())
+        self.peek_utf8(&commands::peek_buried())
+    }
+
+    /// Peeks the next buried job and returns its body as raw bytes
+    pub fn peek_buried_bytes(&mut self) -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        self.peek_cmd(&commands::peek_buried())
     }
 
     /// Delete all the jobs in the ready state
     pub fn delete_all_ready(&mut self) -> BeanstalkdResult<()> {
-        self.delete_all_cmd(Self::peek_ready)
+        self.delete_all_cmd(Self::peek_ready_bytes)
     }
 
     /// Delete all the jobs in the delayed state
     pub fn delete_all_delayed(&mut self) -> BeanstalkdResult<()> {
-        self.delete_all_cmd(Self::peek_delayed)
+        self.delete_all_cmd(Self::peek_delayed_bytes)
     }
 
     /// Delete all the jobs in the buried state
     pub fn delete_all_buried(&mut self) -> BeanstalkdResult<()> {
-        self.delete_all_cmd(Self::peek_buried)
+        self.delete_all_cmd(Self::peek_buried_bytes)
     }
 
     /// Delete all jobs (in any state)
@@ -148,32 +427,192 @@ impl Beanstalkd {
     /// - Ok(Some(_)) if a job is found
     /// - Ok(None) if no job found
     /// - Err(_) if an error occurred
-    fn peek_cmd(&mut self, message: String) -> BeanstalkdResult<Option<(u64, String)>> {
-        self.cmd(message)
-            .map(|r| {
-                if r.status == Status::NOT_FOUND {
-                    None
-                }
-                else {
-                    Some((parse::id(r.clone()), parse::body(r)))
-                }
-            })
+    fn peek_cmd(&mut self, message: &Command) -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+        self.cmd(message).and_then(|response| parse::optional_job(message, response))
+    }
+
+    /// Same as `peek_cmd` but decodes the job body as UTF-8
+    fn peek_utf8(&mut self, message: &Command) -> BeanstalkdResult<Option<(u64, String)>> {
+        self.peek_cmd(message)
+            .and_then(|job| job.map(|job| parse::utf8_job(message, job)).transpose())
     }
 
     fn delete_all_cmd<PeekFn>(&mut self, peek: PeekFn) -> BeanstalkdResult<()>
-        where PeekFn: Fn(&mut Self) -> BeanstalkdResult<Option<(u64, String)>>
+        where PeekFn: Fn(&mut Self) -> BeanstalkdResult<Option<(u64, Vec<u8>)>>
     {
         loop {
             match peek(self)? {
-                Some((job_id, _)) => self.delete(job_id)?,
+                Some((job_id, _)) => {
+                    self.delete(job_id)?;
+                }
                 None => return Ok(()),
             }
         }
     }
 
-    fn cmd(&mut self, message: String) -> BeanstalkdResult<Response> {
+    fn cmd(&mut self, message: &Command) -> BeanstalkdResult<Response> {
+        self.send(message, None)
+    }
+
+    /// Sends a reserve command, whose reply the server holds back for up to `wait` or, if `None`,
+    /// until a job is available
+    fn reserve_cmd(&mut self,
+                   message: &Command,
+                   wait: Option<Duration>)
+                   -> BeanstalkdResult<Response> {
+        self.send(message, Some(wait))
+    }
+
+    /// Sends the command, reconnecting if the connection was lost or left out of sync and a
+    /// reconnect policy is set. The command is sent once more on the new connection if the server
+    /// never got it or if running it twice does no harm. Otherwise the error is returned, as the
+    /// server may have run the command already, e.g. deleted a job reserved by the old connection.
+    fn send(&mut self,
+            message: &Command,
+            wait: Option<Option<Duration>>)
+            -> BeanstalkdResult<Response> {
+        let mut sent = false;
+        match self.send_once(message, wait, &mut sent) {
+            Err(e) if self.reconnect.is_some() && self.broken => {
+                self.reconnect()?;
+                if sent && !message.is_idempotent() {
+                    return Err(e);
+                }
+                self.send_once(message, wait, &mut sent)
+            }
+            result => result,
+        }
+    }
+
+    /// Sends the command on the current connection unless an earlier command left it out of
+    /// sync, in which case it would read the late reply of that command. `sent` is set once the
+    /// whole command was written.
+    fn send_once(&mut self,
+                 message: &Command,
+                 wait: Option<Option<Duration>>,
+                 sent: &mut bool)
+                 -> BeanstalkdResult<Response> {
+        if self.broken {
+            return Err(protocol::connection_broken());
+        }
+        let response = self.send_timed(message, wait, sent);
+        if let Err(e) = &response {
+            self.broken = !protocol::is_server_error(e);
+        }
+        response
+    }
+
+    /// For reserve commands, which come with the time the server may `wait` before replying, the
+    /// read timeout is extended by that time
+    fn send_timed(&mut self,
+                  message: &Command,
+                  wait: Option<Option<Duration>>,
+                  sent: &mut bool)
+                  -> BeanstalkdResult<Response> {
+        let (io, set_read_timeout, wait) = match (&self.timeouts, wait) {
+            (Some(timeouts), Some(wait)) => (timeouts.io, timeouts.set_read_timeout, wait),
+            _ => return self.send_request(message, sent),
+        };
+        set_read_timeout(self.stream.get_ref(), wait.map(|wait| wait.saturating_add(io)))
+            .map_err(BeanstalkdError::ConnectionError)?;
+        let response = self.send_request(message, sent);
+        let restored = set_read_timeout(self.stream.get_ref(), Some(io));
+        response.and_then(|response| {
+            restored.map(|_| response).map_err(BeanstalkdError::ConnectionError)
+        })
+    }
+
+    fn send_request(&mut self, message: &Command, sent: &mut bool) -> BeanstalkdResult<Response> {
         let mut request = Request::new(&mut self.stream);
+        request.write(message)?;
+        *sent = true;
+        request.receive(message)
+    }
+
+    /// Replaces the connection according to the reconnect policy and restores the tubes used and
+    /// watched on the old one
+    fn reconnect(&mut self) -> BeanstalkdResult<()> {
+        let reconnect = match self.reconnect.as_mut() {
+            Some(reconnect) => reconnect,
+            None => return Ok(()),
+        };
+        let mut attempt = 1;
+        let mut backoff = reconnect.policy.initial_backoff;
+        let stream = loop {
+            match (reconnect.connect)() {
+                Ok(stream) => break stream,
+                Err(e) if attempt >= reconnect.policy.max_attempts => return Err(e),
+                Err(_) => {
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(reconnect.policy.max_backoff);
+                    attempt += 1;
+                }
+            }
+        };
+        self.stream = BufStream::new(stream);
+
+        if self.session.used != DEFAULT_TUBE {
+            Request::new(&mut self.stream).send(&commands::tube(&self.session.used))?;
+        }
+        for tube in self.session.watched.iter().filter(|tube| *tube != DEFAULT_TUBE) {
+            Request::new(&mut self.stream).send(&commands::watch(tube))?;
+        }
+        if !self.session.watched.iter().any(|tube| tube == DEFAULT_TUBE) {
+            Request::new(&mut self.stream).send(&commands::ignore(DEFAULT_TUBE))?;
+        }
+        // Only now the new connection matches the session
+        self.broken = false;
+        Ok(())
+    }
+
+    /// Sends the commands pipelined. The replies received before the connection was lost are
+    /// kept and every unanswered command gets an error. If a reconnect policy is set, the
+    /// commands the server never got are sent once more on the new connection, like the ones
+    /// without a reply if running them twice does no harm.
+    fn cmd_many(&mut self,
+                messages: &[Command])
+                -> BeanstalkdResult<Vec<BeanstalkdResult<Response>>> {
+        let mut responses = Vec::with_capacity(messages.len());
+        let mut sent = 0;
+        let error = match self.send_many_once(messages, &mut responses, &mut sent) {
+            Ok(()) => return Ok(responses),
+            Err(e) => e,
+        };
+        if self.reconnect.is_none() || !self.broken {
+            protocol::fail_unanswered(messages, &mut responses, error, messages.len());
+            return Ok(responses);
+        }
+        if let Err(e) = self.reconnect() {
+            protocol::fail_unanswered(messages, &mut responses, e, messages.len());
+            return Ok(responses);
+        }
+        let unanswered = messages.get(responses.len()..sent).unwrap_or_default();
+        if !unanswered.iter().all(Command::is_idempotent) {
+            protocol::fail_unanswered(messages, &mut responses, error, sent);
+        }
+        let unsent = &messages[responses.len()..];
+        if let Err(e) = self.send_many_once(unsent, &mut responses, &mut 0) {
+            protocol::fail_unanswered(messages, &mut responses, e, messages.len());
+        }
+        Ok(responses)
+    }
 
-        request.send(message.as_bytes())
+    /// Sends the commands on the current connection in batches of `PIPELINE_DEPTH`, appending
+    /// their replies to `responses` and counting the commands written in full in `sent`
+    fn send_many_once(&mut self,
+                      messages: &[Command],
+                      responses: &mut Vec<BeanstalkdResult<Response>>,
+                      sent: &mut usize)
+                      -> BeanstalkdResult<()> {
+        if self.broken {
+            return Err(protocol::connection_broken());
+        }
+        for batch in messages.chunks(PIPELINE_DEPTH) {
+            if let Err(e) = Request::new(&mut self.stream).send_all(batch, responses, sent) {
+                self.broken = !protocol::is_server_error(&e);
+                return Err(e);
+            }
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,158 @@
+use std::collections::VecDeque;
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::commands::Command;
+use crate::error::{BeanstalkdError, BeanstalkdResult};
+use crate::protocol::{self, Response};
+
+/// Encodes commands and decodes the server's replies for use with `tokio_util::codec::Framed`.
+///
+/// Replies arrive in the order the commands were sent, so the codec remembers the line of every
+/// encoded command until its reply is decoded and names it in errors. Bodies are collected
+/// across as many reads as it takes to receive them.
+///
+/// Error replies of the server, like `JOB_TOO_BIG`, only fail their command and are decoded as
+/// `Err` items, so a `Framed` stream keeps delivering the replies that follow. Decoding fails
+/// only if the connection is out of sync, which ends the stream.
+#[derive(Debug, Default)]
+pub struct BeanstalkdCodec {
+    decoder: protocol::Decoder,
+    pending: VecDeque<String>,
+}
+
+impl BeanstalkdCodec {
+    pub fn new() -> BeanstalkdCodec {
+        BeanstalkdCodec::default()
+    }
+}
+
+impl<'a> Encoder<Command<'a>> for BeanstalkdCodec {
+    type Error = BeanstalkdError;
+
+    fn encode(&mut self, command: Command<'a>, dst: &mut BytesMut) -> Result<(), BeanstalkdError> {
+        let chunks = command.chunks();
+        dst.reserve(chunks.iter().map(|chunk| chunk.len()).sum());
+        for chunk in chunks.iter() {
+            dst.extend_from_slice(chunk);
+        }
+        self.pending.push_back(command.line().to_string());
+        Ok(())
+    }
+}
+
+impl Decoder for BeanstalkdCodec {
+    type Item = BeanstalkdResult<Response>;
+    type Error = BeanstalkdError;
+
+    fn decode(&mut self,
+              src: &mut BytesMut)
+              -> Result<Option<BeanstalkdResult<Response>>, BeanstalkdError> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+        // Replies which don't answer an encoded command, e.g. when only decoding, are unnamed
+        let command = self.pending.front().map(|line| line.as_str()).unwrap_or_default();
+        let (used, response) = self.decoder.decode(command, src);
+        src.advance(used);
+        match response {
+            Some(Err(e)) if !protocol::is_server_error(&e) => {
+                self.pending.pop_front();
+                Err(e)
+            }
+            Some(response) => {
+                self.pending.pop_front();
+                Ok(Some(response))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self,
+                  src: &mut BytesMut)
+                  -> Result<Option<BeanstalkdResult<Response>>, BeanstalkdError> {
+        match self.decode(src)? {
+            Some(response) => Ok(Some(response)),
+            None if self.decoder.is_partial() => Err(protocol::connection_closed()),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+fn encode(codec: &mut BeanstalkdCodec, command: Command) -> Vec<u8> {
+    let mut dst = BytesMut::new();
+    codec.encode(command, &mut dst).unwrap();
+    dst.to_vec()
+}
+
+#[test]
+fn encode_test() {
+    let mut codec = BeanstalkdCodec::new();
+    assert_eq!(encode(&mut codec, crate::commands::put(b"a\r\nb", 1, 2, 3)),
+               b"put 1 2 3 4\r\na\r\nb\r\n".to_vec());
+    assert_eq!(encode(&mut codec, crate::commands::reserve()), b"reserve\r\n".to_vec());
+}
+
+#[test]
+fn decode_partial_body_test() {
+    let mut codec = BeanstalkdCodec::new();
+    encode(&mut codec, crate::commands::reserve());
+    encode(&mut codec, crate::commands::stats());
+    let input = b"RESERVED 3 6\r\nte\r\nst\r\nOK 8\r\n---\na: b\r\n";
+
+    let mut src = BytesMut::new();
+    let mut responses = Vec::new();
+    for &byte in input.iter() {
+        src.extend_from_slice(&[byte]);
+        if let Some(response) = codec.decode(&mut src).unwrap() {
+            responses.push(response.unwrap());
+        }
+    }
+
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0].status, protocol::Status::RESERVED);
+    assert_eq!(responses[0].body, b"te\r\nst".to_vec());
+    assert_eq!(responses[1].status, protocol::Status::OK);
+    assert_eq!(responses[1].body, b"---\na: b".to_vec());
+    assert!(src.is_empty());
+}
+
+#[test]
+fn decode_several_replies_test() {
+    let mut codec = BeanstalkdCodec::new();
+    let mut src = BytesMut::from(&b"INSERTED 1\r\nINSERTED 2\r\nFOUND 2 1\r\n"[..]);
+    let response = codec.decode(&mut src).unwrap().unwrap().unwrap();
+    assert_eq!(response.arguments, vec!["1".to_string()]);
+    let response = codec.decode(&mut src).unwrap().unwrap().unwrap();
+    assert_eq!(response.arguments, vec!["2".to_string()]);
+    assert!(codec.decode(&mut src).unwrap().is_none());
+    assert!(matches!(codec.decode_eof(&mut src), Err(BeanstalkdError::ConnectionError(_))));
+}
+
+#[test]
+fn decode_error_names_command_test() {
+    let mut codec = BeanstalkdCodec::new();
+    encode(&mut codec, crate::commands::reserve());
+    encode(&mut codec, crate::commands::delete(1));
+    let mut src = BytesMut::from(&b"RESERVED 3 x\r\nDELETED\r\n"[..]);
+    match codec.decode(&mut src) {
+        Err(BeanstalkdError::ParseError { command, .. }) => assert_eq!(command, "reserve"),
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(codec.pending, vec!["delete 1".to_string()]);
+    let response = codec.decode(&mut src).unwrap().unwrap().unwrap();
+    assert_eq!(response.status, protocol::Status::DELETED);
+}
+
+#[test]
+fn decode_server_error_test() {
+    let mut codec = BeanstalkdCodec::new();
+    encode(&mut codec, crate::commands::put(b"a", 0, 0, 60));
+    encode(&mut codec, crate::commands::put(b"b", 0, 0, 60));
+    let mut src = BytesMut::from(&b"JOB_TOO_BIG\r\nINSERTED 2\r\n"[..]);
+    assert!(matches!(codec.decode(&mut src), Ok(Some(Err(BeanstalkdError::JobTooBig)))));
+    let response = codec.decode(&mut src).unwrap().unwrap().unwrap();
+    assert_eq!(response.arguments, vec!["2".to_string()]);
+    assert!(codec.pending.is_empty());
+}
@@ -1,135 +1,306 @@
-pub fn tube(tube: &str) -> String {
-    build("use", vec![tube.to_string()], "")
+//! Builders for the commands understood by the server
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// A command ready to be sent to the server. The body of a `put` is only borrowed and gets
+/// written to the stream as is, without being copied into an intermediate buffer.
+pub struct Command<'a> {
+    line: String,
+    body: Option<&'a [u8]>,
+}
+
+impl<'a> Command<'a> {
+    /// Returns the first line of the command without the trailing `\r\n`
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Returns the parts of the command in the order they go on the wire, so writers other than
+    /// `std::io::Write` can send it without copying the body
+    pub fn chunks(&self) -> [&[u8]; 4] {
+        let line_break = b"\r\n";
+        match self.body {
+            Some(body) => [self.line.as_bytes(), line_break, body, line_break],
+            None => [self.line.as_bytes(), line_break, b"", b""],
+        }
+    }
+
+    /// Writes the command line followed by the body, if any, to `writer`
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for chunk in self.chunks().iter() {
+            writer.write_all(chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if running the command twice does no harm, so it may be sent once more
+    /// when its reply was lost. Reservations end with the connection, which makes reserving
+    /// again on a new connection safe as well.
+    pub(crate) fn is_idempotent(&self) -> bool {
+        let name = self.line.split(' ').next().unwrap_or_default();
+        matches!(name, "use" | "watch" | "ignore") ||
+        ["reserve", "stats", "peek", "list-"].iter().any(|prefix| name.starts_with(prefix))
+    }
 }
 
-pub fn put(body: &str, priority: u32, delay: u32, ttr: u32) -> String {
+pub fn tube(tube: &str) -> Command<'static> {
+    build("use", vec![tube.to_string()], None)
+}
+
+pub fn put(body: &[u8], priority: u32, delay: u32, ttr: u32) -> Command<'_> {
     build("put",
           vec![priority.to_string(), delay.to_string(), ttr.to_string()],
-          body)
+          Some(body))
+}
+
+pub fn reserve() -> Command<'static> {
+    build("reserve", vec![], None)
+}
+
+pub fn reserve_with_timeout(timeout: u64) -> Command<'static> {
+    build("reserve-with-timeout", vec![timeout.to_string()], None)
+}
+
+pub fn reserve_job(id: u64) -> Command<'static> {
+    build("reserve-job", vec![id.to_string()], None)
+}
+
+pub fn delete(id: u64) -> Command<'static> {
+    build("delete", vec![id.to_string()], None)
+}
+
+pub fn release(id: u64, priority: u32, delay: u32) -> Command<'static> {
+    build("release", vec![id.to_string(), priority.to_string(), delay.to_string()], None)
+}
+
+pub fn bury(id: u64, priority: u32) -> Command<'static> {
+    build("bury", vec![id.to_string(), priority.to_string()], None)
+}
+
+pub fn touch(id: u64) -> Command<'static> {
+    build("touch", vec![id.to_string()], None)
 }
 
-pub fn reserve() -> String {
-    build("reserve", vec![], "")
+pub fn kick(bound: u64) -> Command<'static> {
+    build("kick", vec![bound.to_string()], None)
 }
 
-pub fn reserve_with_timeout(timeout: u64) -> String {
-    build("reserve-with-timeout", vec![timeout.to_string()], "")
+pub fn kick_job(id: u64) -> Command<'static> {
+    build("kick-job", vec![id.to_string()], None)
 }
 
-pub fn delete(id: u64) -> String {
-    build("delete", vec![id.to_string()], "")
+pub fn stats() -> Command<'static> {
+    build("stats", vec![], None)
 }
 
-pub fn release(id: u64, priority: u32, delay: u32) -> String {
-    build("release", vec![id.to_string(), priority.to_string(), delay.to_string()], "")
+pub fn stats_job(id: u64) -> Command<'static> {
+    build("stats-job", vec![id.to_string()], None)
 }
 
-pub fn bury(id: u64, priority: u32) -> String {
-    build("bury", vec![id.to_string(), priority.to_string()], "")
+pub fn stats_tube(tube: &str) -> Command<'static> {
+    build("stats-tube", vec![tube.to_string()], None)
 }
 
-pub fn touch(id: u64) -> String {
-    build("touch", vec![id.to_string()], "")
+pub fn watch(tube: &str) -> Command<'static> {
+    build("watch", vec![tube.to_string()], None)
 }
 
-pub fn stats() -> String {
-    build("stats", vec![], "")
+pub fn ignore(tube: &str) -> Command<'static> {
+    build("ignore", vec![tube.to_string()], None)
 }
 
-pub fn stats_job(id: u64) -> String {
-    build("stats-job", vec![id.to_string()], "")
+pub fn list_tubes() -> Command<'static> {
+    build("list-tubes", vec![], None)
 }
 
-pub fn watch(tube: &str) -> String {
-    build("watch", vec![tube.to_string()], "")
+pub fn list_tube_used() -> Command<'static> {
+    build("list-tube-used", vec![], None)
 }
 
-pub fn ignore(tube: &str) -> String {
-    build("ignore", vec![tube.to_string()], "")
+pub fn list_tubes_watched() -> Command<'static> {
+    build("list-tubes-watched", vec![], None)
 }
 
-pub fn peek_ready() -> String {
-    build("peek-ready", vec![], "")
+/// The delay is sent in whole seconds, partial seconds are rounded up
+pub fn pause_tube(tube: &str, delay: Duration) -> Command<'static> {
+    let seconds = delay.as_secs().saturating_add(if delay.subsec_nanos() > 0 { 1 } else { 0 });
+    build("pause-tube", vec![tube.to_string(), seconds.to_string()], None)
 }
 
-pub fn peek_delayed() -> String {
-    build("peek-delayed", vec![], "")
+pub fn peek(id: u64) -> Command<'static> {
+    build("peek", vec![id.to_string()], None)
 }
 
-pub fn peek_buried() -> String {
-    build("peek-buried", vec![], "")
+pub fn peek_ready() -> Command<'static> {
+    build("peek-ready", vec![], None)
 }
 
-fn build(op: &str, args: Vec<String>, body: &str) -> String {
-    let line_break = "\r\n";
+pub fn peek_delayed() -> Command<'static> {
+    build("peek-delayed", vec![], None)
+}
+
+pub fn peek_buried() -> Command<'static> {
+    build("peek-buried", vec![], None)
+}
+
+fn build<'a>(op: &str, args: Vec<String>, body: Option<&'a [u8]>) -> Command<'a> {
     let space = " ";
-    let mut message = String::new() + op;
+    let mut line = String::new() + op;
 
-    if args.len() > 0 {
-        message = message + space + &(args.join(space));
+    if !args.is_empty() {
+        line = line + space + &(args.join(space));
     }
 
-    if body.len() > 0 {
-        message = message + space + &(body.len().to_string()) + line_break + body;
+    if let Some(body) = body {
+        line = line + space + &(body.len().to_string());
     }
 
-    message = message + line_break;
+    Command { line, body }
+}
 
+#[cfg(test)]
+fn encode(command: Command) -> Vec<u8> {
+    let mut message = Vec::new();
+    command.write_to(&mut message).unwrap();
     message
 }
 
 #[test]
 fn tube_test() {
-    assert_eq!(tube("custom_tube"), "use custom_tube\r\n".to_string());
+    assert_eq!(encode(tube("custom_tube")), b"use custom_tube\r\n".to_vec());
 }
 
 #[test]
 fn put_test() {
-    assert_eq!(put("some message", 0, 2, 10000),
-               "put 0 2 10000 12\r\nsome message\r\n".to_string());
+    assert_eq!(encode(put(b"some message", 0, 2, 10000)),
+               b"put 0 2 10000 12\r\nsome message\r\n".to_vec());
+}
+
+#[test]
+fn put_binary_test() {
+    assert_eq!(encode(put(&[0xff, 0x00, 0x0d, 0x0a], 0, 0, 60)),
+               b"put 0 0 60 4\r\n\xff\x00\r\n\r\n".to_vec());
+}
+
+#[test]
+fn put_empty_test() {
+    assert_eq!(encode(put(b"", 0, 0, 60)), b"put 0 0 60 0\r\n\r\n".to_vec());
+}
+
+#[test]
+fn put_borrows_body_test() {
+    let body = b"some message".to_vec();
+    let command = put(&body, 0, 0, 60);
+    assert_eq!(command.line(), "put 0 0 60 12");
+    assert_eq!(command.body.unwrap().as_ptr(), body.as_ptr());
+}
+
+#[test]
+fn is_idempotent_test() {
+    assert!(tube("jobs").is_idempotent());
+    assert!(reserve_with_timeout(1).is_idempotent());
+    assert!(stats_tube("jobs").is_idempotent());
+    assert!(peek_ready().is_idempotent());
+    assert!(list_tubes_watched().is_idempotent());
+    assert!(!put(b"job", 0, 0, 60).is_idempotent());
+    assert!(!delete(1).is_idempotent());
+    assert!(!release(1, 0, 0).is_idempotent());
+    assert!(!kick(10).is_idempotent());
+    assert!(!pause_tube("jobs", Duration::from_secs(10)).is_idempotent());
 }
 
 #[test]
 fn reserve_test() {
-    assert_eq!(reserve(), "reserve\r\n".to_string());
+    assert_eq!(encode(reserve()), b"reserve\r\n".to_vec());
 }
 
 #[test]
 fn reserve_with_timeout_test() {
-    assert_eq!(reserve_with_timeout(10), "reserve-with-timeout 10\r\n".to_string())
+    assert_eq!(encode(reserve_with_timeout(10)), b"reserve-with-timeout 10\r\n".to_vec())
+}
+
+#[test]
+fn reserve_job_test() {
+    assert_eq!(encode(reserve_job(7)), b"reserve-job 7\r\n".to_vec())
 }
 
 #[test]
 fn delete_test() {
-    assert_eq!(delete(1), "delete 1\r\n".to_string());
+    assert_eq!(encode(delete(1)), b"delete 1\r\n".to_vec());
 }
 
 #[test]
 fn release_test() {
-    assert_eq!(release(1, 1024, 10), "release 1 1024 10\r\n".to_string());
+    assert_eq!(encode(release(1, 1024, 10)), b"release 1 1024 10\r\n".to_vec());
 }
 
 #[test]
 fn bury_test() {
-    assert_eq!(bury(1, 1024), "bury 1 1024\r\n".to_string());
+    assert_eq!(encode(bury(1, 1024)), b"bury 1 1024\r\n".to_vec());
 }
 
 #[test]
 fn touch_test() {
-    assert_eq!(touch(1), "touch 1\r\n".to_string());
+    assert_eq!(encode(touch(1)), b"touch 1\r\n".to_vec());
+}
+
+#[test]
+fn kick_test() {
+    assert_eq!(encode(kick(100)), b"kick 100\r\n".to_vec());
+}
+
+#[test]
+fn kick_job_test() {
+    assert_eq!(encode(kick_job(1)), b"kick-job 1\r\n".to_vec());
 }
 
 #[test]
 fn stats_test() {
-    assert_eq!(stats(), "stats\r\n".to_string());
+    assert_eq!(encode(stats()), b"stats\r\n".to_vec());
+}
+
+#[test]
+fn stats_tube_test() {
+    assert_eq!(encode(stats_tube("hello_tube")), b"stats-tube hello_tube\r\n".to_vec());
 }
 
 #[test]
 fn watch_test() {
-    assert_eq!(watch("hello_tube"), "watch hello_tube\r\n".to_string());
+    assert_eq!(encode(watch("hello_tube")), b"watch hello_tube\r\n".to_vec());
 }
 
 #[test]
 fn ignore_test() {
-    assert_eq!(ignore("hello_tube"), "ignore hello_tube\r\n".to_string());
+    assert_eq!(encode(ignore("hello_tube")), b"ignore hello_tube\r\n".to_vec());
+}
+
+#[test]
+fn list_tubes_test() {
+    assert_eq!(encode(list_tubes()), b"list-tubes\r\n".to_vec());
+}
+
+#[test]
+fn list_tube_used_test() {
+    assert_eq!(encode(list_tube_used()), b"list-tube-used\r\n".to_vec());
+}
+
+#[test]
+fn list_tubes_watched_test() {
+    assert_eq!(encode(list_tubes_watched()), b"list-tubes-watched\r\n".to_vec());
+}
+
+#[test]
+fn pause_tube_test() {
+    assert_eq!(encode(pause_tube("hello_tube", Duration::from_secs(30))),
+               b"pause-tube hello_tube 30\r\n".to_vec());
+    assert_eq!(encode(pause_tube("hello_tube", Duration::from_millis(1500))),
+               b"pause-tube hello_tube 2\r\n".to_vec());
+    assert_eq!(pause_tube("hello_tube", Duration::MAX).line(),
+               format!("pause-tube hello_tube {}", u64::MAX));
+}
+
+#[test]
+fn peek_test() {
+    assert_eq!(encode(peek(1)), b"peek 1\r\n".to_vec());
 }
@@ -1,32 +1,126 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::io;
+use std::str::Utf8Error;
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug)]
 pub enum BeanstalkdError {
-    ConnectionError,
+    /// Connecting to the server failed or the server closed the connection
+    ConnectionError(io::Error),
+    /// Sending the command or reading its reply failed
+    RequestError {
+        command: String,
+        source: io::Error,
+    },
+    /// The reply to the command is not valid UTF-8
+    Utf8Error {
+        command: String,
+        source: Utf8Error,
+    },
+    /// A value in the reply to the command couldn't be parsed
+    ParseError {
+        command: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// The server replied with a status this client doesn't know
     UnknownStatusError(String),
-    RequestError,
+    /// The connection URL is malformed or not supported
+    InvalidUrl(String),
+    /// Setting up a TLS connection failed, e.g. because of an invalid certificate
+    #[cfg(feature = "tls")]
+    TlsError(Box<dyn Error + Send + Sync>),
+    /// The reply to the command doesn't follow the protocol's framing, e.g. a body without the
+    /// trailing `\r\n`
+    ProtocolError {
+        command: String,
+        message: String,
+    },
+    /// The server cannot allocate enough memory for the job
+    OutOfMemory,
+    /// The server hit an internal bug
+    InternalError,
+    /// The command line was malformed
+    BadFormat,
+    /// The server doesn't know the command
+    UnknownCommand,
+    /// The job body must be followed by `\r\n`
+    ExpectedCrlf,
+    /// The job body is larger than the server's `max-job-size`
+    JobTooBig,
+    /// The server is in drain mode and no longer accepts new jobs
+    Draining,
+    /// A job reserved by this client is about to exceed its time to run
+    DeadlineSoon,
 }
 
 impl Error for BeanstalkdError {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
-            BeanstalkdError::ConnectionError => "Connection error occurred",
-            BeanstalkdError::RequestError => "Request error occurred",
-            BeanstalkdError::UnknownStatusError(_) => "Unknown status",
+            BeanstalkdError::ConnectionError(source) => Some(source),
+            BeanstalkdError::RequestError { source, .. } => Some(source),
+            BeanstalkdError::Utf8Error { source, .. } => Some(source),
+            BeanstalkdError::ParseError { source, .. } => Some(&**source),
+            #[cfg(feature = "tls")]
+            BeanstalkdError::TlsError(source) => Some(&**source),
+            _ => None,
         }
     }
 }
 
+/// Lets I/O errors of transports which only know about `BeanstalkdError`, like codecs, be
+/// converted with `?`
+impl From<io::Error> for BeanstalkdError {
+    fn from(error: io::Error) -> BeanstalkdError {
+        BeanstalkdError::ConnectionError(error)
+    }
+}
+
 impl Display for BeanstalkdError {
     fn fmt(&self, formatter: &mut Formatter) -> ::std::fmt::Result {
-        let message = match self {
-            BeanstalkdError::ConnectionError => "Connection error occurred".to_string(),
-            BeanstalkdError::RequestError => "Request error occurred".to_string(),
-            BeanstalkdError::UnknownStatusError(status) => format!("Unknown status: {}", status),
-        };
-        message.fmt(formatter)
+        match self {
+            BeanstalkdError::ConnectionError(source) => {
+                write!(formatter, "Connection error occurred: {}", source)
+            }
+            BeanstalkdError::RequestError { command, source } => {
+                write!(formatter, "Request error occurred in `{}`: {}", command, source)
+            }
+            BeanstalkdError::Utf8Error { command, source } => {
+                write!(formatter, "Reply to `{}` is not valid UTF-8: {}", command, source)
+            }
+            BeanstalkdError::ParseError { command, source } => {
+                write!(formatter, "Reply to `{}` couldn't be parsed: {}", command, source)
+            }
+            BeanstalkdError::UnknownStatusError(status) => {
+                write!(formatter, "Unknown status: {}", status)
+            }
+            BeanstalkdError::ProtocolError { command, message } => {
+                write!(formatter, "Malformed reply to `{}`: {}", command, message)
+            }
+            BeanstalkdError::InvalidUrl(message) => write!(formatter, "Invalid URL: {}", message),
+            #[cfg(feature = "tls")]
+            BeanstalkdError::TlsError(source) => write!(formatter, "TLS error: {}", source),
+            BeanstalkdError::OutOfMemory => "Server is out of memory".fmt(formatter),
+            BeanstalkdError::InternalError => "Server internal error".fmt(formatter),
+            BeanstalkdError::BadFormat => "Badly formatted command".fmt(formatter),
+            BeanstalkdError::UnknownCommand => "Unknown command".fmt(formatter),
+            BeanstalkdError::ExpectedCrlf => "Job body not terminated by CRLF".fmt(formatter),
+            BeanstalkdError::JobTooBig => "Job body too big".fmt(formatter),
+            BeanstalkdError::Draining => "Server is draining".fmt(formatter),
+            BeanstalkdError::DeadlineSoon => "Reserved job deadline soon".fmt(formatter),
+        }
     }
 }
 
 pub type BeanstalkdResult<T> = Result<T, BeanstalkdError>;
+
+#[test]
+fn source_test() {
+    let error = BeanstalkdError::RequestError {
+        command: "reserve".to_string(),
+        source: io::Error::new(io::ErrorKind::ConnectionReset, "reset"),
+    };
+    assert_eq!(error.to_string(), "Request error occurred in `reserve`: reset");
+    assert_eq!(error.source().unwrap().to_string(), "reset");
+
+    assert!(BeanstalkdError::JobTooBig.source().is_none());
+}
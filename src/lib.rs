@@ -1,11 +1,31 @@
 //! # Easy-to-use beanstalkd client for Rust (IronMQ compatible)
 
+#[cfg(feature = "async")]
+pub use async_beanstalkd::AsyncBeanstalkd;
 pub use beanstalkd::Beanstalkd;
+#[cfg(feature = "codec")]
+pub use codec::BeanstalkdCodec;
 pub use error::{BeanstalkdError, BeanstalkdResult};
+pub use options::{ConnectOptions, ReconnectPolicy};
+pub use response::ReserveOutcome;
+pub use stats::{JobState, JobStats, ServerStats, TubeStats};
+#[cfg(feature = "tls")]
+pub use tls::{TlsConfig, TlsStream};
+pub use url::{AnyStream, URL_VARIABLE};
 
+#[cfg(feature = "async")]
+mod async_beanstalkd;
 mod beanstalkd;
-mod commands;
+#[cfg(feature = "codec")]
+mod codec;
+pub mod commands;
 mod error;
+mod options;
 mod parse;
+pub mod protocol;
 mod request;
 mod response;
+mod stats;
+#[cfg(feature = "tls")]
+mod tls;
+mod url;
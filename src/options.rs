@@ -0,0 +1,209 @@
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::Path;
+use std::time::Duration;
+use socket2::{SockRef, TcpKeepalive};
+
+use crate::beanstalkd::{Beanstalkd, Reconnect, Timeouts};
+use crate::error::{BeanstalkdError, BeanstalkdResult};
+
+/// Settings for opening a connection, used like
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use beanstalkd::ConnectOptions;
+/// let mut beanstalkd = ConnectOptions::new()
+///     .connect_timeout(Duration::from_secs(5))
+///     .io_timeout(Duration::from_secs(10))
+///     .connect("localhost", 11300)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ConnectOptions {
+    connect_timeout: Option<Duration>,
+    io_timeout: Option<Duration>,
+    nodelay: bool,
+    keepalive: Option<Duration>,
+    reconnect: Option<ReconnectPolicy>,
+}
+
+/// How often and how fast to try reconnecting after the connection was lost. The delay between
+/// attempts starts at the initial backoff and doubles up to the maximum backoff.
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    pub(crate) max_attempts: u32,
+    pub(crate) initial_backoff: Duration,
+    pub(crate) max_backoff: Duration,
+}
+
+impl ReconnectPolicy {
+    /// Up to 5 attempts, starting 100 milliseconds apart and at most 10 seconds apart
+    pub fn new() -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+
+    /// Gives up after `attempts` failed attempts to connect
+    pub fn max_attempts(mut self, attempts: u32) -> ReconnectPolicy {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Waits `backoff` after the first failed attempt
+    pub fn initial_backoff(mut self, backoff: Duration) -> ReconnectPolicy {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Never waits longer than `backoff` between attempts
+    pub fn max_backoff(mut self, backoff: Duration) -> ReconnectPolicy {
+        self.max_backoff = backoff;
+        self
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> ReconnectPolicy {
+        ReconnectPolicy::new()
+    }
+}
+
+impl ConnectOptions {
+    /// Options without any timeouts, like `Beanstalkd::connect`
+    pub fn new() -> ConnectOptions {
+        ConnectOptions::default()
+    }
+
+    /// Gives up connecting to each address of the host after `timeout`
+    pub fn connect_timeout(mut self, timeout: Duration) -> ConnectOptions {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Fails a command with a `RequestError` if sending it or receiving its reply stalls for
+    /// longer than `timeout`. The time the server may hold back a reply to `reserve` or
+    /// `reserve_with_timeout` while waiting for a job doesn't count. As the reply may still
+    /// arrive, later commands fail with a `ConnectionError` unless a reconnect policy replaces
+    /// the connection.
+    pub fn io_timeout(mut self, timeout: Duration) -> ConnectOptions {
+        self.io_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets `TCP_NODELAY`, sending small commands right away instead of coalescing them
+    pub fn nodelay(mut self, nodelay: bool) -> ConnectOptions {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Enables TCP keepalive probes after the connection was idle for `idle`, so a vanished
+    /// server is noticed even while waiting for a job
+    pub fn keepalive(mut self, idle: Duration) -> ConnectOptions {
+        self.keepalive = Some(idle);
+        self
+    }
+
+    /// Opens a new connection when the current one is lost or left out of sync. The tubes used
+    /// and watched are selected again on the new connection. A failed command is sent once more
+    /// if the server never got it or if running it twice does no harm, like `use`, `reserve`,
+    /// `peek` or `stats`. Otherwise, e.g. for `put` or `delete` whose reply was lost, the error is
+    /// returned, as the server may have run the command already.
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> ConnectOptions {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    /// Connect to a running beanstalkd server with these options
+    pub fn connect(&self, host: &str, port: u16) -> BeanstalkdResult<Beanstalkd> {
+        let tcp_stream = self.open_tcp(host, port)?;
+        let timeouts = self.io_timeout.map(|io| {
+            Timeouts {
+                io,
+                set_read_timeout: TcpStream::set_read_timeout,
+            }
+        });
+        let mut beanstalkd = Beanstalkd::with_timeouts(tcp_stream, timeouts);
+
+        if let Some(policy) = &self.reconnect {
+            let options = self.clone();
+            let host = host.to_string();
+            beanstalkd.set_reconnect(Reconnect {
+                policy: policy.clone(),
+                connect: Box::new(move || options.open_tcp(&host, port)),
+            });
+        }
+        Ok(beanstalkd)
+    }
+
+    /// Connect to a beanstalkd server listening on a Unix domain socket with these options. Only
+    /// the I/O timeout and the reconnect policy apply to such connections.
+    #[cfg(unix)]
+    pub fn connect_unix<P: AsRef<Path>>(&self,
+                                        path: P)
+                                        -> BeanstalkdResult<Beanstalkd<UnixStream>> {
+        let unix_stream = self.open_unix(path.as_ref())?;
+        let timeouts = self.io_timeout.map(|io| {
+            Timeouts {
+                io,
+                set_read_timeout: UnixStream::set_read_timeout,
+            }
+        });
+        let mut beanstalkd = Beanstalkd::with_timeouts(unix_stream, timeouts);
+
+        if let Some(policy) = &self.reconnect {
+            let options = self.clone();
+            let path = path.as_ref().to_path_buf();
+            beanstalkd.set_reconnect(Reconnect {
+                policy: policy.clone(),
+                connect: Box::new(move || options.open_unix(&path)),
+            });
+        }
+        Ok(beanstalkd)
+    }
+
+    fn open_tcp(&self, host: &str, port: u16) -> BeanstalkdResult<TcpStream> {
+        let tcp_stream = self.connect_tcp(host, port).map_err(BeanstalkdError::ConnectionError)?;
+        tcp_stream.set_nodelay(self.nodelay).map_err(BeanstalkdError::ConnectionError)?;
+        if let Some(idle) = self.keepalive {
+            SockRef::from(&tcp_stream)
+                .set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))
+                .map_err(BeanstalkdError::ConnectionError)?;
+        }
+        tcp_stream.set_write_timeout(self.io_timeout).map_err(BeanstalkdError::ConnectionError)?;
+        tcp_stream.set_read_timeout(self.io_timeout).map_err(BeanstalkdError::ConnectionError)?;
+
+        Ok(tcp_stream)
+    }
+
+    #[cfg(unix)]
+    fn open_unix(&self, path: &Path) -> BeanstalkdResult<UnixStream> {
+        let unix_stream = UnixStream::connect(path).map_err(BeanstalkdError::ConnectionError)?;
+        unix_stream.set_write_timeout(self.io_timeout).map_err(BeanstalkdError::ConnectionError)?;
+        unix_stream.set_read_timeout(self.io_timeout).map_err(BeanstalkdError::ConnectionError)?;
+        Ok(unix_stream)
+    }
+
+    /// Tries every address of the host in turn, like `TcpStream::connect`
+    fn connect_tcp(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let timeout = match self.connect_timeout {
+            Some(timeout) => timeout,
+            None => return TcpStream::connect((host, port)),
+        };
+        let mut last_error = None;
+        for address in (host, port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(tcp_stream) => return Ok(tcp_stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "host has no addresses")
+        }))
+    }
+}
@@ -1,81 +1,337 @@
 use std::collections::HashMap;
-use std::str::FromStr;
 
-use response::Response;
+use crate::commands::Command;
+use crate::error::{BeanstalkdError, BeanstalkdResult};
+use crate::response::{ReserveOutcome, Response, Status};
 
-pub fn id(response: Response) -> u64 {
-    let line_segments: Vec<&str> = response.data.trim().split(' ').collect();
-    let id: u64 = FromStr::from_str(line_segments[1]).unwrap();
-    id
+pub fn id(command: &Command, response: Response) -> BeanstalkdResult<u64> {
+    number(command, &response, 0)
 }
 
-pub fn body(response: Response) -> String {
-    let body_start = response.data.trim().find('\n').unwrap() + 1;
-    response.data.trim()[body_start..].to_string()
+/// Returns the id and the body of a job, moving the body out of the response
+pub fn job(command: &Command, response: Response) -> BeanstalkdResult<(u64, Vec<u8>)> {
+    let id = number(command, &response, 0)?;
+    Ok((id, response.body))
 }
 
-pub fn hashmap(response: Response) -> HashMap<String, String> {
+/// Returns the job of a reply or `None` if the server reported that there is no such job or that
+/// the reserve timed out
+pub fn optional_job(command: &Command,
+                    response: Response)
+                    -> BeanstalkdResult<Option<(u64, Vec<u8>)>> {
+    match response.status {
+        Status::NOT_FOUND | Status::TIMED_OUT => Ok(None),
+        _ => job(command, response).map(Some),
+    }
+}
+
+/// Turns the reply to a reserve command into an outcome. `DEADLINE_SOON` arrives as an error
+/// from the connection and is mapped to an outcome here as well.
+pub fn reserve_outcome(command: &Command,
+                       result: BeanstalkdResult<Response>)
+                       -> BeanstalkdResult<ReserveOutcome<Vec<u8>>> {
+    match result {
+        Ok(response) => {
+            optional_job(command, response).map(|job| match job {
+                Some((id, body)) => ReserveOutcome::Job(id, body),
+                None => ReserveOutcome::TimedOut,
+            })
+        }
+        Err(BeanstalkdError::DeadlineSoon) => Ok(ReserveOutcome::DeadlineSoon),
+        Err(e) => Err(e),
+    }
+}
+
+/// Decodes the body of a job returned by `command` as UTF-8
+pub fn utf8_job(command: &Command,
+                (id, body): (u64, Vec<u8>))
+                -> BeanstalkdResult<(u64, String)> {
+    String::from_utf8(body)
+        .map(|body| (id, body))
+        .map_err(|e| {
+            BeanstalkdError::Utf8Error {
+                command: command.line().to_string(),
+                source: e.utf8_error(),
+            }
+        })
+}
+
+pub fn utf8_outcome(command: &Command,
+                    outcome: ReserveOutcome<Vec<u8>>)
+                    -> BeanstalkdResult<ReserveOutcome> {
+    match outcome {
+        ReserveOutcome::Job(id, body) => {
+            utf8_job(command, (id, body)).map(|(id, body)| ReserveOutcome::Job(id, body))
+        }
+        ReserveOutcome::TimedOut => Ok(ReserveOutcome::TimedOut),
+        ReserveOutcome::DeadlineSoon => Ok(ReserveOutcome::DeadlineSoon),
+    }
+}
+
+pub fn hashmap(response: Response) -> BeanstalkdResult<HashMap<String, String>> {
     let mut map = HashMap::new();
-    for line in response.data.trim().split('\n').skip(2) {
-        let line_segments: Vec<&str> = line.trim().split(": ").collect();
-        let key = line_segments[0].to_string();
-        let value = line_segments[1].to_string();
-        map.insert(key, value);
+    for line in String::from_utf8_lossy(&response.body).lines() {
+        // Lines which aren't `key: value` pairs carry no stats
+        if let Some((key, value)) = line.split_once(':') {
+            map.insert(key.trim().to_string(), value.trim().to_string());
+        }
     }
-    map
+    Ok(map)
 }
 
-pub fn count(response: Response) -> Option<u64> {
-    let line_segments: Vec<&str> = response.data.trim().split(' ').collect();
-    if line_segments.len() == 1 {
-        None
+pub fn list(response: Response) -> BeanstalkdResult<Vec<String>> {
+    Ok(String::from_utf8_lossy(&response.body).lines()
+        .filter_map(|line| line.strip_prefix("- "))
+        .map(|name| name.trim().to_string())
+        .collect())
+}
+
+pub fn name(command: &Command, response: Response) -> BeanstalkdResult<String> {
+    argument(command, &response, 0).map(|name| name.to_string())
+}
+
+pub fn count(command: &Command, response: Response) -> BeanstalkdResult<Option<u64>> {
+    if response.arguments.is_empty() {
+        Ok(None)
     } else {
-        let id: u64 = FromStr::from_str(line_segments[1]).unwrap();
-        Some(id)
+        number(command, &response, 0).map(Some)
+    }
+}
+
+/// Returns the argument at `index`, the first one following the status being at index 0
+fn argument<'a>(command: &Command,
+                response: &'a Response,
+                index: usize)
+                -> BeanstalkdResult<&'a str> {
+    response.arguments
+        .get(index)
+        .map(|argument| argument.as_str())
+        .ok_or_else(|| {
+            let message = format!("missing argument {} of {:?}", index + 1, response.status);
+            parse_error(command, message)
+        })
+}
+
+fn number(command: &Command, response: &Response, index: usize) -> BeanstalkdResult<u64> {
+    let argument = argument(command, response, index)?;
+    argument.parse().map_err(|_| {
+        let message = format!("invalid number `{}` in {:?}", argument, response.status);
+        parse_error(command, message)
+    })
+}
+
+fn parse_error(command: &Command, message: String) -> BeanstalkdError {
+    BeanstalkdError::ParseError {
+        command: command.line().to_string(),
+        source: message.into(),
+    }
+}
+
+#[cfg(test)]
+fn command() -> Command<'static> {
+    crate::commands::reserve()
+}
+
+#[cfg(test)]
+fn response(status: crate::response::Status, header: &str, body: &[u8]) -> Response {
+    Response {
+        status,
+        arguments: header.split(' ').skip(1).map(|argument| argument.to_string()).collect(),
+        body: body.to_vec(),
     }
 }
 
 #[test]
 fn id_test() {
-    let response = Response {
-        status: ::response::Status::INSERTED,
-        data: "INSERTED 3\r\n".to_string(),
-    };
-    assert_eq!(id(response), 3);
+    let response = response(Status::INSERTED, "INSERTED 3", b"");
+    assert_eq!(id(&command(), response).unwrap(), 3);
+}
+
+#[test]
+fn malformed_id_test() {
+    let put = crate::commands::put(b"x", 0, 0, 60);
+    let missing = response(Status::INSERTED, "INSERTED", b"");
+    match id(&put, missing) {
+        Err(BeanstalkdError::ParseError { command, .. }) => assert_eq!(command, "put 0 0 60 1"),
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let invalid = response(Status::INSERTED, "INSERTED x3", b"");
+    match id(&put, invalid) {
+        Err(BeanstalkdError::ParseError { command, source }) => {
+            assert_eq!(command, "put 0 0 60 1");
+            assert_eq!(source.to_string(), "invalid number `x3` in INSERTED")
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn job_test() {
+    let response = response(Status::RESERVED, "RESERVED 3 10", b"test\r\nbody");
+    assert_eq!(job(&command(), response).unwrap(), (3, b"test\r\nbody".to_vec()));
+}
+
+#[test]
+fn job_whitespace_test() {
+    let response = response(Status::RESERVED, "RESERVED 3 9", b" {\"a\":1}\n");
+    assert_eq!(job(&command(), response).unwrap(), (3, b" {\"a\":1}\n".to_vec()));
+}
+
+#[test]
+fn binary_job_test() {
+    let response = response(Status::RESERVED, "RESERVED 3 4", b"\xff\x00\xfe\x01");
+    assert_eq!(job(&command(), response).unwrap(), (3, vec![0xff, 0x00, 0xfe, 0x01]));
 }
 
 #[test]
-fn body_test() {
-    let response = Response {
-        status: ::response::Status::RESERVED,
-        data: "RESERVED 3 4\r\ntest\r\nbody\r\n".to_string(),
-    };
-    assert_eq!(body(response), "test\r\nbody".to_string());
+fn empty_job_test() {
+    let response = response(Status::FOUND, "FOUND 7 0", b"");
+    assert_eq!(job(&command(), response).unwrap(), (7, vec![]));
+}
+
+#[test]
+fn job_body_is_moved_test() {
+    let response = response(Status::RESERVED, "RESERVED 3 4", b"test");
+    let buffer = response.body.as_ptr();
+    let (_, body) = job(&command(), response).unwrap();
+    assert_eq!(body.as_ptr(), buffer);
+}
+
+#[test]
+fn optional_job_test() {
+    let found = response(Status::FOUND, "FOUND 7 4", b"test");
+    let command = command();
+    assert_eq!(optional_job(&command, found).unwrap(), Some((7, b"test".to_vec())));
+    let not_found = response(Status::NOT_FOUND, "NOT_FOUND", b"");
+    assert_eq!(optional_job(&command, not_found).unwrap(), None);
+    let timed_out = response(Status::TIMED_OUT, "TIMED_OUT", b"");
+    assert_eq!(optional_job(&command, timed_out).unwrap(), None);
+}
+
+#[test]
+fn reserve_outcome_test() {
+    let reserved = response(Status::RESERVED, "RESERVED 3 4", b"test");
+    let command = command();
+    assert_eq!(reserve_outcome(&command, Ok(reserved)).unwrap(),
+               ReserveOutcome::Job(3, b"test".to_vec()));
+    let timed_out = response(Status::TIMED_OUT, "TIMED_OUT", b"");
+    assert_eq!(reserve_outcome(&command, Ok(timed_out)).unwrap(), ReserveOutcome::TimedOut);
+    assert_eq!(reserve_outcome(&command, Err(BeanstalkdError::DeadlineSoon)).unwrap(),
+               ReserveOutcome::DeadlineSoon);
+    assert!(reserve_outcome(&command, Err(BeanstalkdError::JobTooBig)).is_err());
+}
+
+#[test]
+fn utf8_job_test() {
+    let command = crate::commands::peek(3);
+    assert_eq!(utf8_job(&command, (3, b"test".to_vec())).unwrap(), (3, "test".to_string()));
+    match utf8_job(&command, (3, vec![0xff])) {
+        Err(BeanstalkdError::Utf8Error { command, .. }) => assert_eq!(command, "peek 3"),
+        other => panic!("unexpected result {:?}", other),
+    }
 }
 
 #[test]
 fn hashmap_test() {
-    let response = Response {
-        status: ::response::Status::INSERTED,
-        data: "OK 15\r\n---\r\na: b\r\nc: d\r\n".to_string(),
-    };
+    let response = response(Status::OK, "OK 15", b"---\na: b\nc: d\n");
     let mut expected_hashmap = HashMap::new();
     expected_hashmap.insert("a".to_string(), "b".to_string());
     expected_hashmap.insert("c".to_string(), "d".to_string());
-    assert_eq!(hashmap(response), expected_hashmap);
+    assert_eq!(hashmap(response).unwrap(), expected_hashmap);
+}
+
+#[test]
+fn hashmap_without_separator_test() {
+    let response = response(Status::OK, "OK 14", b"---\na: b\nc\ne:\n");
+    let mut expected_hashmap = HashMap::new();
+    expected_hashmap.insert("a".to_string(), "b".to_string());
+    expected_hashmap.insert("e".to_string(), "".to_string());
+    assert_eq!(hashmap(response).unwrap(), expected_hashmap);
+}
+
+#[test]
+fn list_test() {
+    let response = response(Status::OK, "OK 24", b"---\n- default\n- my_tube\n");
+    assert_eq!(list(response).unwrap(), vec!["default".to_string(), "my_tube".to_string()]);
+}
+
+#[test]
+fn name_test() {
+    let response = response(Status::USING, "USING my_tube", b"");
+    assert_eq!(name(&command(), response).unwrap(), "my_tube".to_string());
 }
 
 #[test]
 fn count_test() {
-    let response_ok = Response {
-        status: ::response::Status::WATCHING,
-        data: "WATCHING 2\r\n".to_string(),
-    };
-    assert_eq!(count(response_ok), Some(2));
-
-    let response_fail = Response {
-        status: ::response::Status::NOT_IGNORED,
-        data: "NOT_IGNORED\r\n".to_string(),
-    };
-    assert_eq!(count(response_fail), None);
+    let response_ok = response(Status::WATCHING, "WATCHING 2", b"");
+    assert_eq!(count(&command(), response_ok).unwrap(), Some(2));
+
+    let response_fail = response(Status::NOT_IGNORED, "NOT_IGNORED", b"");
+    assert_eq!(count(&command(), response_fail).unwrap(), None);
+}
+
+/// Deterministic xorshift generator so failures can be reproduced
+#[cfg(test)]
+pub(crate) struct Random(pub u64);
+
+#[cfg(test)]
+impl Random {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn bytes(&mut self, alphabet: &[u8]) -> Vec<u8> {
+        let len = self.next() % 64;
+        (0..len)
+            .map(|_| {
+                // Mix bytes likely to appear in replies with completely random ones
+                if self.next().is_multiple_of(4) {
+                    self.next() as u8
+                } else {
+                    alphabet[(self.next() % alphabet.len() as u64) as usize]
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+fn parse_all(header: &[u8], payload: &[u8]) {
+    let response = response(Status::OK, &String::from_utf8_lossy(header), payload);
+    let command = command();
+    let _ = id(&command, response.clone());
+    let _ = job(&command, response.clone());
+    let _ = hashmap(response.clone());
+    let _ = list(response.clone());
+    let _ = name(&command, response.clone());
+    let _ = count(&command, response);
+}
+
+#[test]
+fn random_input_test() {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    let alphabet = b"RESERVED OK 0123456789\r\n---:- \xff";
+    for _ in 0..10_000 {
+        parse_all(&random.bytes(alphabet), &random.bytes(alphabet));
+    }
+}
+
+#[test]
+fn truncated_input_test() {
+    let replies: [(&[u8], &[u8]); 4] = [(b"RESERVED 12 11", b"hello world"),
+                                        (b"OK 19", b"---\na: b\nc: d\n"),
+                                        (b"USING default", b""),
+                                        (b"WATCHING 2", b"")];
+    for &(header, payload) in replies.iter() {
+        for end in 0..header.len() {
+            parse_all(&header[..end], payload);
+        }
+        for end in 0..payload.len() {
+            parse_all(header, &payload[..end]);
+        }
+    }
 }
@@ -0,0 +1,439 @@
+//! Transport independent implementation of the beanstalkd reply format. The decoder never does
+//! any I/O itself: bytes read from any kind of connection are handed to it and it returns the
+//! replies it could complete, so every client and test harness shares the same decoding.
+
+use std::error::Error;
+use std::io;
+use std::mem;
+use std::str;
+
+use crate::commands::Command;
+use crate::error::{BeanstalkdError, BeanstalkdResult};
+pub use crate::response::{Response, Status};
+
+/// The number of commands the clients' batch methods send at once. Replies are read only after
+/// the whole batch was written, so it's kept small enough for the replies to fit into the socket
+/// buffers instead of blocking the server while it writes them.
+pub(crate) const PIPELINE_DEPTH: usize = 256;
+
+/// The most body bytes reserved up front, the default `max-job-size` of the server plus the
+/// trailing `\r\n`. The byte count comes from the server, so buffers for larger bodies grow as
+/// the bytes arrive instead of allocating whatever size a broken reply announces.
+const PREALLOCATED_BODY_LEN: usize = 65_535 + 2;
+
+/// The longest first line of a reply accepted, including the trailing `\r\n`. It's the size of
+/// the server's own line buffer, which holds tube names of up to 200 bytes, the longest argument
+/// of any reply. Bytes are collected until the end of the line arrives, so a peer never sending
+/// it can't make the decoder grow without bound.
+const MAX_LINE_LEN: usize = 224;
+
+/// Incrementally decodes replies of the server.
+///
+/// Replies are decoded one at a time. Pass every chunk of bytes received to `decode` together
+/// with the line of the command that is being answered, which is used in error messages. The
+/// decoder reports how many bytes it used, which is never more than the current reply needs, so
+/// bytes belonging to the following reply stay with the caller.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    /// The part of the first line of the reply received so far
+    line: Vec<u8>,
+    /// A reply whose first line was decoded but whose body (including the trailing `\r\n`) is
+    /// still incomplete, together with the number of body bytes expected
+    pending: Option<(Response, usize)>,
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder::default()
+    }
+
+    /// Decodes as much of `input` as needed to complete the reply to `command`. Returns the
+    /// number of bytes used and the reply if it is complete. When the reply is incomplete all
+    /// of `input` has been used and the next bytes of the connection have to be passed.
+    ///
+    /// Error replies of the server, like `JOB_TOO_BIG`, are complete replies as well and leave
+    /// the connection in sync. After any other error the connection most likely is out of sync.
+    pub fn decode(&mut self,
+                  command: &str,
+                  input: &[u8])
+                  -> (usize, Option<BeanstalkdResult<Response>>) {
+        let mut used = 0;
+
+        if self.pending.is_none() {
+            let end = input.iter().position(|&byte| byte == b'\n').map(|position| position + 1);
+            if self.line.len() + end.unwrap_or(input.len()) > MAX_LINE_LEN {
+                self.line.clear();
+                let message = format!("reply line longer than {} bytes", MAX_LINE_LEN);
+                return (end.unwrap_or(input.len()), Some(Err(protocol_error(command, message))));
+            }
+            let end = match end {
+                Some(end) => end,
+                None => {
+                    self.line.extend_from_slice(input);
+                    return (input.len(), None);
+                }
+            };
+            self.line.extend_from_slice(&input[..end]);
+            used = end;
+
+            match self.decode_line(command) {
+                Ok(None) => {}
+                Ok(Some(response)) => return (used, Some(Ok(response))),
+                Err(e) => return (used, Some(Err(e))),
+            }
+        }
+
+        if let Some((response, expected)) = self.pending.as_mut() {
+            let missing = *expected - response.body.len();
+            let available = missing.min(input.len() - used);
+            response.body.extend_from_slice(&input[used..used + available]);
+            used += available;
+            if available == missing {
+                if let Some((mut response, _)) = self.pending.take() {
+                    let result = strip_crlf(command, &mut response.body).map(|_| response);
+                    return (used, Some(result));
+                }
+            }
+        }
+
+        (used, None)
+    }
+
+    /// Returns `true` if part of a reply has been received but not completed yet
+    pub fn is_partial(&self) -> bool {
+        !self.line.is_empty() || self.pending.is_some()
+    }
+
+    /// Decodes the complete first line of a reply. Returns the reply unless it announced a body,
+    /// which is then awaited.
+    fn decode_line(&mut self, command: &str) -> BeanstalkdResult<Option<Response>> {
+        let line = mem::take(&mut self.line);
+        let line = str::from_utf8(&line).map_err(|source| {
+            BeanstalkdError::Utf8Error {
+                command: command.to_string(),
+                source,
+            }
+        })?;
+        let (mut response, body_len) = parse_header(command, line)?;
+        match body_len {
+            Some(body_len) => {
+                // +2 needed for trailing line break
+                let expected = body_len.checked_add(2).ok_or_else(|| {
+                    protocol_error(command, format!("byte count {} too large", body_len))
+                })?;
+                // The body is collected straight into the buffer handed to the caller
+                response.body.reserve_exact(expected.min(PREALLOCATED_BODY_LEN));
+                self.pending = Some((response, expected));
+                Ok(None)
+            }
+            None => Ok(Some(response)),
+        }
+    }
+}
+
+/// Decodes the first line of a reply to `command`. Returns the response with an empty body and,
+/// if the status announces a payload, the number of bytes that follow the line without the
+/// trailing `\r\n`.
+fn parse_header(command: &str, line: &str) -> BeanstalkdResult<(Response, Option<usize>)> {
+    let parse_error = |source: Box<dyn Error + Send + Sync>| {
+        BeanstalkdError::ParseError {
+            command: command.to_string(),
+            source,
+        }
+    };
+
+    let mut line_segments = line.trim_end_matches(['\r', '\n']).split(' ');
+    let status = parse_status(line_segments.next().unwrap_or_default())?;
+    let arguments: Vec<String> = line_segments.map(|segment| segment.to_string()).collect();
+
+    // These status codes indicate that there's a payload to decode
+    let argument_offset_opt = match status {
+        Status::OK => Some(0),
+        Status::RESERVED | Status::FOUND => Some(1),
+
+        _ => None,
+    };
+
+    let body_len = match argument_offset_opt {
+        Some(argument_offset) => {
+            let bytes_count_str = arguments.get(argument_offset)
+                .ok_or_else(|| parse_error("missing byte count".into()))?;
+            let bytes_count: usize = bytes_count_str.parse()
+                .map_err(|e| parse_error(Box::new(e)))?;
+            Some(bytes_count)
+        }
+        None => None,
+    };
+
+    let response = Response {
+        status,
+        arguments,
+        body: Vec::new(),
+    };
+    Ok((response, body_len))
+}
+
+/// Removes the `\r\n` the server sends after every body
+fn strip_crlf(command: &str, body: &mut Vec<u8>) -> BeanstalkdResult<()> {
+    if !body.ends_with(b"\r\n") {
+        return Err(protocol_error(command, "body not terminated by CRLF".to_string()));
+    }
+    let len = body.len() - 2;
+    body.truncate(len);
+    Ok(())
+}
+
+fn protocol_error(command: &str, message: String) -> BeanstalkdError {
+    BeanstalkdError::ProtocolError {
+        command: command.to_string(),
+        message,
+    }
+}
+
+/// Returns `true` if the error is a complete reply of the server, which fails the command but
+/// leaves the connection usable for the following ones
+pub(crate) fn is_server_error(error: &BeanstalkdError) -> bool {
+    matches!(error,
+             BeanstalkdError::OutOfMemory | BeanstalkdError::InternalError |
+             BeanstalkdError::BadFormat | BeanstalkdError::UnknownCommand |
+             BeanstalkdError::ExpectedCrlf | BeanstalkdError::JobTooBig |
+             BeanstalkdError::Draining | BeanstalkdError::DeadlineSoon)
+}
+
+/// The error returned when sending `command` or reading its reply failed
+pub(crate) fn request_error(command: &Command, source: io::Error) -> BeanstalkdError {
+    BeanstalkdError::RequestError {
+        command: command.line().to_string(),
+        source,
+    }
+}
+
+/// Fails the commands of a batch from the first one without a reply in `responses` up to `end`.
+/// The first gets the `error` which broke the connection, the others an error telling that their
+/// reply never arrived.
+pub(crate) fn fail_unanswered(commands: &[Command],
+                              responses: &mut Vec<BeanstalkdResult<Response>>,
+                              error: BeanstalkdError,
+                              end: usize) {
+    responses.push(Err(error));
+    let unanswered = commands.get(responses.len()..end).unwrap_or_default();
+    responses.extend(unanswered.iter().map(|command| {
+        let source = io::Error::new(io::ErrorKind::NotConnected,
+                                    "connection lost before the reply arrived");
+        Err(request_error(command, source))
+    }));
+}
+
+/// The error returned when the server closed the connection instead of replying
+pub(crate) fn connection_closed() -> BeanstalkdError {
+    let source = io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed by server");
+    BeanstalkdError::ConnectionError(source)
+}
+
+/// The error returned for commands on a connection which an earlier command left out of sync
+pub(crate) fn connection_broken() -> BeanstalkdError {
+    let source = io::Error::new(io::ErrorKind::NotConnected,
+                                "connection out of sync after an earlier error");
+    BeanstalkdError::ConnectionError(source)
+}
+
+/// Maps the first word of a reply to its status. Error replies of the server are turned into
+/// the matching `BeanstalkdError`.
+fn parse_status(word: &str) -> BeanstalkdResult<Status> {
+    match word {
+        "OK" => Ok(Status::OK),
+        "RESERVED" => Ok(Status::RESERVED),
+        "INSERTED" => Ok(Status::INSERTED),
+        "USING" => Ok(Status::USING),
+        "DELETED" => Ok(Status::DELETED),
+        "WATCHING" => Ok(Status::WATCHING),
+        "NOT_IGNORED" => Ok(Status::NOT_IGNORED),
+        "NOT_FOUND" => Ok(Status::NOT_FOUND),
+        "FOUND" => Ok(Status::FOUND),
+        "TIMED_OUT" => Ok(Status::TIMED_OUT),
+        "RELEASED" => Ok(Status::RELEASED),
+        "BURIED" => Ok(Status::BURIED),
+        "TOUCHED" => Ok(Status::TOUCHED),
+        "KICKED" => Ok(Status::KICKED),
+        "PAUSED" => Ok(Status::PAUSED),
+        "OUT_OF_MEMORY" => Err(BeanstalkdError::OutOfMemory),
+        "INTERNAL_ERROR" => Err(BeanstalkdError::InternalError),
+        "BAD_FORMAT" => Err(BeanstalkdError::BadFormat),
+        "UNKNOWN_COMMAND" => Err(BeanstalkdError::UnknownCommand),
+        "EXPECTED_CRLF" => Err(BeanstalkdError::ExpectedCrlf),
+        "JOB_TOO_BIG" => Err(BeanstalkdError::JobTooBig),
+        "DRAINING" => Err(BeanstalkdError::Draining),
+        "DEADLINE_SOON" => Err(BeanstalkdError::DeadlineSoon),
+        other => Err(BeanstalkdError::UnknownStatusError(other.to_string())),
+    }
+}
+
+#[test]
+fn parse_status_test() {
+    assert_eq!(parse_status("INSERTED").unwrap(), Status::INSERTED);
+    assert_eq!(parse_status("KICKED").unwrap(), Status::KICKED);
+    assert_eq!(parse_status("NOT_FOUND").unwrap(), Status::NOT_FOUND);
+}
+
+#[test]
+fn parse_status_error_test() {
+    macro_rules! assert_error {
+        ($word:expr, $error:pat) => (assert!(matches!(parse_status($word), Err($error))))
+    }
+    assert_error!("OUT_OF_MEMORY", BeanstalkdError::OutOfMemory);
+    assert_error!("INTERNAL_ERROR", BeanstalkdError::InternalError);
+    assert_error!("BAD_FORMAT", BeanstalkdError::BadFormat);
+    assert_error!("UNKNOWN_COMMAND", BeanstalkdError::UnknownCommand);
+    assert_error!("EXPECTED_CRLF", BeanstalkdError::ExpectedCrlf);
+    assert_error!("JOB_TOO_BIG", BeanstalkdError::JobTooBig);
+    assert_error!("DRAINING", BeanstalkdError::Draining);
+    assert_error!("DEADLINE_SOON", BeanstalkdError::DeadlineSoon);
+    match parse_status("WAT") {
+        Err(BeanstalkdError::UnknownStatusError(status)) => assert_eq!(status, "WAT"),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn parse_header_test() {
+    let command = "reserve";
+    let (response, body_len) = parse_header(command, "RESERVED 5 11\r\n").unwrap();
+    assert_eq!(response.status, Status::RESERVED);
+    assert_eq!(response.arguments, vec!["5".to_string(), "11".to_string()]);
+    assert_eq!(body_len, Some(11));
+
+    let (response, body_len) = parse_header(command, "TIMED_OUT\r\n").unwrap();
+    assert_eq!(response.status, Status::TIMED_OUT);
+    assert_eq!(body_len, None);
+}
+
+#[test]
+fn parse_header_malformed_test() {
+    let command = "reserve";
+    match parse_header(command, "RESERVED 5 many\r\n") {
+        Err(BeanstalkdError::ParseError { command, .. }) => assert_eq!(command, "reserve"),
+        other => panic!("unexpected result {:?}", other.map(|(_, body_len)| body_len)),
+    }
+    assert!(parse_header(command, "RESERVED 5\r\n").is_err());
+}
+
+#[test]
+fn strip_crlf_test() {
+    let mut body = b" body\n\r\n".to_vec();
+    strip_crlf("reserve", &mut body).unwrap();
+    assert_eq!(body, b" body\n".to_vec());
+
+    let mut body = b"body\n\n".to_vec();
+    match strip_crlf("reserve", &mut body) {
+        Err(BeanstalkdError::ProtocolError { command, .. }) => assert_eq!(command, "reserve"),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[cfg(test)]
+fn decode_all(input: &[u8], chunk_size: usize) -> Vec<Response> {
+    let mut decoder = Decoder::new();
+    let mut responses = Vec::new();
+    for chunk in input.chunks(chunk_size) {
+        let mut chunk = chunk;
+        while !chunk.is_empty() {
+            let (used, response) = decoder.decode("reserve", chunk);
+            responses.extend(response.map(|response| response.unwrap()));
+            chunk = &chunk[used..];
+        }
+    }
+    assert!(!decoder.is_partial());
+    responses
+}
+
+#[test]
+fn decode_test() {
+    let mut decoder = Decoder::new();
+    let (used, response) = decoder.decode("reserve", b"RESERVED 3 4\r\ntest\r\nINSERTED");
+    assert_eq!(used, 20);
+    let response = response.unwrap().unwrap();
+    assert_eq!(response.status, Status::RESERVED);
+    assert_eq!(response.body, b"test".to_vec());
+    assert!(!decoder.is_partial());
+}
+
+#[test]
+fn decode_in_chunks_test() {
+    let input = b"RESERVED 3 6\r\nte\r\nst\r\nTIMED_OUT\r\nFOUND 4 0\r\n\r\nDELETED\r\n";
+    for chunk_size in 1..input.len() + 1 {
+        let responses = decode_all(input, chunk_size);
+        let statuses: Vec<Status> = responses.iter().map(|r| r.status.clone()).collect();
+        assert_eq!(statuses,
+                   vec![Status::RESERVED, Status::TIMED_OUT, Status::FOUND, Status::DELETED]);
+        assert_eq!(responses[0].body, b"te\r\nst".to_vec());
+        assert_eq!(responses[2].body, b"".to_vec());
+    }
+}
+
+#[test]
+fn decode_error_test() {
+    let mut decoder = Decoder::new();
+    let (used, response) = decoder.decode("reserve", b"RESERVED 3 2\r\nokay");
+    assert_eq!(used, 18);
+    assert!(matches!(response, Some(Err(BeanstalkdError::ProtocolError { .. }))));
+    assert!(!decoder.is_partial());
+
+    let (used, response) = decoder.decode("put 0 0 1 1", b"JOB_TOO_BIG\r\nINSERTED 1\r\n");
+    assert_eq!(used, 13);
+    assert!(matches!(response, Some(Err(BeanstalkdError::JobTooBig))));
+
+    let (used, response) = decoder.decode("reserve", b"RESERVED 1 18446744073709551615\r\n");
+    assert_eq!(used, 33);
+    assert!(matches!(response, Some(Err(BeanstalkdError::ProtocolError { .. }))));
+    assert!(!decoder.is_partial());
+
+    let (used, response) = decoder.decode("reserve", b"RESERVED 1 99999999999999\r\nbody");
+    assert_eq!((used, response.is_none()), (31, true));
+    match &decoder.pending {
+        Some((response, expected)) => {
+            assert_eq!(*expected, 100_000_000_000_001);
+            assert!(response.body.capacity() <= PREALLOCATED_BODY_LEN);
+        }
+        None => panic!("reply not pending"),
+    }
+
+    // The announced body never arrives, so start over
+    let mut decoder = Decoder::new();
+    let (used, response) = decoder.decode("reserve", &[b'O'; 200]);
+    assert_eq!((used, response.is_none()), (200, true));
+    let (used, response) = decoder.decode("reserve", &[b'K'; 200]);
+    assert_eq!(used, 200);
+    assert!(matches!(response, Some(Err(BeanstalkdError::ProtocolError { .. }))));
+    assert!(!decoder.is_partial());
+
+    match decoder.decode("reserve", b"\xff\r\n") {
+        (3, Some(Err(BeanstalkdError::Utf8Error { command, .. }))) => {
+            assert_eq!(command, "reserve")
+        }
+        (used, other) => panic!("unexpected result {} {:?}", used, other),
+    }
+}
+
+#[test]
+fn random_input_test() {
+    let mut random = crate::parse::Random(0x9e37_79b9_7f4a_7c15);
+    let alphabet = b"RESERVED FOUND OK 0123456789\r\n\xff";
+    let mut inputs: Vec<Vec<u8>> = vec![b"RESERVED 1 18446744073709551615\r\n".to_vec(),
+                                        b"RESERVED 1 99999999999999\r\n".to_vec(),
+                                        b"OK 18446744073709551614\r\n".to_vec(),
+                                        vec![b'O'; 100_000]];
+    inputs.extend((0..10_000).map(|_| random.bytes(alphabet)));
+    for input in inputs {
+        let mut decoder = Decoder::new();
+        let chunk_size = (random.next() % 8 + 1) as usize;
+        for chunk in input.chunks(chunk_size) {
+            let mut chunk = chunk;
+            while !chunk.is_empty() {
+                let (used, _) = decoder.decode("reserve", chunk);
+                assert!(used <= chunk.len());
+                assert!(decoder.line.len() <= MAX_LINE_LEN);
+                chunk = &chunk[used..];
+            }
+        }
+    }
+}
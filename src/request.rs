@@ -1,89 +1,72 @@
-extern crate bufstream;
-
-use std::net::TcpStream;
-use self::bufstream::BufStream;
+use bufstream::BufStream;
 use std::io::{Write, BufRead, Read};
-use std::str::FromStr;
-use std::str::from_utf8;
-
-use error::{BeanstalkdError, BeanstalkdResult};
-use response::{Response, Status};
-
-macro_rules! try {
-    ($e:expr) => (match $e { Ok(e) => e, Err(_) => return Err(BeanstalkdError::RequestError) })
-}
 
-macro_rules! try_option {
-    ($e:expr) => (match $e { Some(e) => e, None => return Err(BeanstalkdError::RequestError) })
-}
+use crate::commands::Command;
+use crate::error::BeanstalkdResult;
+use crate::protocol::{self, request_error, Decoder, Response};
 
-pub struct Request<'a> {
-    stream: &'a mut BufStream<TcpStream>,
+pub struct Request<'a, S: Read + Write> {
+    stream: &'a mut BufStream<S>,
 }
 
-impl<'a> Request<'a> {
-    pub fn new<'b>(stream: &'b mut BufStream<TcpStream>) -> Request {
-        Request { stream: stream }
+impl<'a, S: Read + Write> Request<'a, S> {
+    pub fn new(stream: &mut BufStream<S>) -> Request<'_, S> {
+        Request { stream }
     }
 
-    pub fn send(&mut self, message: &[u8]) -> BeanstalkdResult<Response> {
-        let _ = self.stream.write(message);
-        let _ = self.stream.flush();
+    pub fn send(&mut self, command: &Command) -> BeanstalkdResult<Response> {
+        self.write(command)?;
 
-        let mut line = String::new();
-        match self.stream.read_line(&mut line) {
-            Ok(bytes_read) => {
-                // Zero bytes read indicates the TCP connection was closed.
-                if bytes_read == 0 {
-                    return Err(BeanstalkdError::ConnectionError)
-                }
-            },
-            Err(_) => return Err(BeanstalkdError::RequestError)
-        };
-        let line_segments: Vec<&str> = line.trim().split(' ').collect();
-        let status_str = try_option!(line_segments.first());
-        let status = match *status_str {
-            "OK" => Status::OK,
-            "RESERVED" => Status::RESERVED,
-            "INSERTED" => Status::INSERTED,
-            "USING" => Status::USING,
-            "DELETED" => Status::DELETED,
-            "WATCHING" => Status::WATCHING,
-            "NOT_IGNORED" => Status::NOT_IGNORED,
-            "NOT_FOUND" => Status::NOT_FOUND,
-            "FOUND" => Status::FOUND,
-            "TIMED_OUT" => Status::TIMED_OUT,
-            "RELEASED" => Status::RELEASED,
-            "BURIED" => Status::BURIED,
-            "TOUCHED" => Status::TOUCHED,
-            other => {
-                dbg!(&status_str);
-                return Err(BeanstalkdError::UnknownStatusError(other.to_string()));
-            },
-        };
-        let mut data = line.clone();
+        self.receive(command)
+    }
 
-        // These status codes indicate that there's a payload to decode
-        let segment_offset_opt = match status {
-            Status::OK => Some(1),
-            Status::RESERVED | Status::FOUND => Some(2),
+    /// Writes the command and flushes the stream. If this fails the server didn't get the whole
+    /// command, so it didn't run it.
+    pub fn write(&mut self, command: &Command) -> BeanstalkdResult<()> {
+        command.write_to(self.stream).map_err(|e| request_error(command, e))?;
+        self.stream.flush().map_err(|e| request_error(command, e))
+    }
 
-            _ => None,
-        };
+    /// Sends all commands before reading the first reply, so the batch costs a single round
+    /// trip. Appends the replies to `responses` in the order of the commands and adds the number
+    /// of commands written in full, which the server may have run, to `sent`. Error replies of
+    /// the server only fail their own command, any other error fails the rest of the batch as the
+    /// connection can't be trusted anymore. The replies received until then are kept.
+    pub fn send_all(&mut self,
+                    commands: &[Command],
+                    responses: &mut Vec<BeanstalkdResult<Response>>,
+                    sent: &mut usize)
+                    -> BeanstalkdResult<()> {
+        for command in commands {
+            command.write_to(self.stream).map_err(|e| request_error(command, e))?;
+            *sent += 1;
+        }
+        if let Some(command) = commands.last() {
+            self.stream.flush().map_err(|e| request_error(command, e))?;
+        }
 
-        if let Some(segment_offset) = segment_offset_opt {
-            let bytes_count_str = try_option!(line_segments.get(segment_offset));
-            let bytes_count: usize = try!(FromStr::from_str(*bytes_count_str));
-            let mut tmp_vec: Vec<u8> = vec![0; bytes_count + 2]; // +2 needed for trailing line break
-            let payload_utf8 = &mut tmp_vec[..];
-            try!(self.stream.read_exact(payload_utf8));
-            let payload_str = try!(from_utf8(&payload_utf8));
-            data = data + &payload_str;
+        for command in commands {
+            match self.receive(command) {
+                Err(e) if !protocol::is_server_error(&e) => return Err(e),
+                response => responses.push(response),
+            }
         }
+        Ok(())
+    }
 
-        Ok(Response {
-            status: status,
-            data: data,
-        })
+    pub fn receive(&mut self, command: &Command) -> BeanstalkdResult<Response> {
+        let mut decoder = Decoder::new();
+        loop {
+            let input = self.stream.fill_buf().map_err(|e| request_error(command, e))?;
+            // Zero bytes read indicates the TCP connection was closed.
+            if input.is_empty() {
+                return Err(protocol::connection_closed());
+            }
+            let (used, response) = decoder.decode(command.line(), input);
+            self.stream.consume(used);
+            if let Some(response) = response {
+                return response;
+            }
+        }
     }
 }
@@ -1,4 +1,4 @@
-#[allow(non_camel_case_types)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 #[derive(PartialEq, Debug, Clone)]
 pub enum Status {
     OK,
@@ -12,12 +12,30 @@ pub enum Status {
     RELEASED,
     BURIED,
     TOUCHED,
+    KICKED,
+    PAUSED,
     NOT_FOUND,
     FOUND,
 }
 
-#[derive(Clone)]
+/// A decoded reply. The body is read into its own buffer once and then moved, never copied.
+#[derive(Debug, Clone)]
 pub struct Response {
     pub status: Status,
-    pub data: String,
+    /// Space separated arguments following the status on the first line of the reply
+    pub arguments: Vec<String>,
+    /// Exactly the number of bytes announced in the header, empty if the reply has no body
+    pub body: Vec<u8>,
+}
+
+/// The result of a reserve command which didn't fail
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ReserveOutcome<B = String> {
+    /// A job was reserved
+    Job(u64, B),
+    /// No job became available before the timeout ran out
+    TimedOut,
+    /// A job reserved by this client is about to exceed its time to run. It should be touched,
+    /// released or deleted before reserving the next one.
+    DeadlineSoon,
 }
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::commands::Command;
+use crate::error::{BeanstalkdError, BeanstalkdResult};
+
+/// Server-wide statistics as returned by `stats`
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ServerStats {
+    /// Number of ready jobs with priority < 1024
+    pub current_jobs_urgent: u64,
+    /// Number of jobs in the ready queue
+    pub current_jobs_ready: u64,
+    /// Number of jobs reserved by all clients
+    pub current_jobs_reserved: u64,
+    /// Number of delayed jobs
+    pub current_jobs_delayed: u64,
+    /// Number of buried jobs
+    pub current_jobs_buried: u64,
+    /// Cumulative number of put commands
+    pub cmd_put: u64,
+    /// Cumulative number of peek commands
+    pub cmd_peek: u64,
+    /// Cumulative number of peek-ready commands
+    pub cmd_peek_ready: u64,
+    /// Cumulative number of peek-delayed commands
+    pub cmd_peek_delayed: u64,
+    /// Cumulative number of peek-buried commands
+    pub cmd_peek_buried: u64,
+    /// Cumulative number of reserve commands
+    pub cmd_reserve: u64,
+    /// Cumulative number of reserve-with-timeout commands
+    pub cmd_reserve_with_timeout: u64,
+    /// Cumulative number of touch commands
+    pub cmd_touch: u64,
+    /// Cumulative number of use commands
+    pub cmd_use: u64,
+    /// Cumulative number of watch commands
+    pub cmd_watch: u64,
+    /// Cumulative number of ignore commands
+    pub cmd_ignore: u64,
+    /// Cumulative number of delete commands
+    pub cmd_delete: u64,
+    /// Cumulative number of release commands
+    pub cmd_release: u64,
+    /// Cumulative number of bury commands
+    pub cmd_bury: u64,
+    /// Cumulative number of kick commands
+    pub cmd_kick: u64,
+    /// Cumulative number of stats commands
+    pub cmd_stats: u64,
+    /// Cumulative number of stats-job commands
+    pub cmd_stats_job: u64,
+    /// Cumulative number of stats-tube commands
+    pub cmd_stats_tube: u64,
+    /// Cumulative number of list-tubes commands
+    pub cmd_list_tubes: u64,
+    /// Cumulative number of list-tube-used commands
+    pub cmd_list_tube_used: u64,
+    /// Cumulative number of list-tubes-watched commands
+    pub cmd_list_tubes_watched: u64,
+    /// Cumulative number of pause-tube commands
+    pub cmd_pause_tube: u64,
+    /// Cumulative count of times a job has timed out
+    pub job_timeouts: u64,
+    /// Cumulative count of jobs created
+    pub total_jobs: u64,
+    /// Maximum number of bytes in a job
+    pub max_job_size: u64,
+    /// Number of currently-existing tubes
+    pub current_tubes: u64,
+    /// Number of currently open connections
+    pub current_connections: u64,
+    /// Number of open connections that have each issued at least one put command
+    pub current_producers: u64,
+    /// Number of open connections that have each issued at least one reserve command
+    pub current_workers: u64,
+    /// Number of open connections that have issued a reserve command but not yet received a
+    /// response
+    pub current_waiting: u64,
+    /// Cumulative count of connections
+    pub total_connections: u64,
+    /// Process id of the server
+    pub pid: u64,
+    /// Version string of the server
+    pub version: String,
+    /// Cumulative user CPU time of the server process
+    pub rusage_utime: Duration,
+    /// Cumulative system CPU time of the server process
+    pub rusage_stime: Duration,
+    /// Time since the server started
+    pub uptime: Duration,
+    /// Index of the oldest binlog file needed to store the current jobs
+    pub binlog_oldest_index: u64,
+    /// Index of the current binlog file being written to
+    pub binlog_current_index: u64,
+    /// Cumulative number of records written as part of compaction
+    pub binlog_records_migrated: u64,
+    /// Cumulative number of records written to the binlog
+    pub binlog_records_written: u64,
+    /// Maximum size in bytes a binlog file is allowed to get
+    pub binlog_max_size: u64,
+    /// Whether the server is in drain mode
+    pub draining: bool,
+    /// Random id string of the server process
+    pub id: String,
+    /// Hostname of the machine running the server
+    pub hostname: String,
+    /// Operating system of the machine running the server
+    pub os: String,
+    /// Machine architecture of the server
+    pub platform: String,
+    /// Fields returned by the server which are not known to this client
+    pub extra: HashMap<String, String>,
+}
+
+impl ServerStats {
+    pub(crate) fn from_map(map: HashMap<String, String>,
+                           command: &Command)
+                           -> BeanstalkdResult<ServerStats> {
+        let mut fields = Fields(map, command.line());
+        Ok(ServerStats {
+            current_jobs_urgent: fields.number("current-jobs-urgent")?,
+            current_jobs_ready: fields.number("current-jobs-ready")?,
+            current_jobs_reserved: fields.number("current-jobs-reserved")?,
+            current_jobs_delayed: fields.number("current-jobs-delayed")?,
+            current_jobs_buried: fields.number("current-jobs-buried")?,
+            cmd_put: fields.number("cmd-put")?,
+            cmd_peek: fields.number("cmd-peek")?,
+            cmd_peek_ready: fields.number("cmd-peek-ready")?,
+            cmd_peek_delayed: fields.number("cmd-peek-delayed")?,
+            cmd_peek_buried: fields.number("cmd-peek-buried")?,
+            cmd_reserve: fields.number("cmd-reserve")?,
+            cmd_reserve_with_timeout: fields.number("cmd-reserve-with-timeout")?,
+            cmd_touch: fields.number("cmd-touch")?,
+            cmd_use: fields.number("cmd-use")?,
+            cmd_watch: fields.number("cmd-watch")?,
+            cmd_ignore: fields.number("cmd-ignore")?,
+            cmd_delete: fields.number("cmd-delete")?,
+            cmd_release: fields.number("cmd-release")?,
+            cmd_bury: fields.number("cmd-bury")?,
+            cmd_kick: fields.number("cmd-kick")?,
+            cmd_stats: fields.number("cmd-stats")?,
+            cmd_stats_job: fields.number("cmd-stats-job")?,
+            cmd_stats_tube: fields.number("cmd-stats-tube")?,
+            cmd_list_tubes: fields.number("cmd-list-tubes")?,
+            cmd_list_tube_used: fields.number("cmd-list-tube-used")?,
+            cmd_list_tubes_watched: fields.number("cmd-list-tubes-watched")?,
+            cmd_pause_tube: fields.number("cmd-pause-tube")?,
+            job_timeouts: fields.number("job-timeouts")?,
+            total_jobs: fields.number("total-jobs")?,
+            max_job_size: fields.number("max-job-size")?,
+            current_tubes: fields.number("current-tubes")?,
+            current_connections: fields.number("current-connections")?,
+            current_producers: fields.number("current-producers")?,
+            current_workers: fields.number("current-workers")?,
+            current_waiting: fields.number("current-waiting")?,
+            total_connections: fields.number("total-connections")?,
+            pid: fields.number("pid")?,
+            version: fields.string("version"),
+            rusage_utime: fields.fractional_seconds("rusage-utime")?,
+            rusage_stime: fields.fractional_seconds("rusage-stime")?,
+            uptime: fields.seconds("uptime")?,
+            binlog_oldest_index: fields.number("binlog-oldest-index")?,
+            binlog_current_index: fields.number("binlog-current-index")?,
+            binlog_records_migrated: fields.number("binlog-records-migrated")?,
+            binlog_records_written: fields.number("binlog-records-written")?,
+            binlog_max_size: fields.number("binlog-max-size")?,
+            draining: fields.number("draining")?,
+            id: fields.string("id"),
+            hostname: fields.string("hostname"),
+            os: fields.string("os"),
+            platform: fields.string("platform"),
+            extra: fields.0,
+        })
+    }
+}
+
+/// Statistics of a single tube as returned by `stats-tube`
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TubeStats {
+    /// Name of the tube
+    pub name: String,
+    /// Number of ready jobs with priority < 1024
+    pub current_jobs_urgent: u64,
+    /// Number of jobs in the ready queue
+    pub current_jobs_ready: u64,
+    /// Number of jobs reserved by all clients
+    pub current_jobs_reserved: u64,
+    /// Number of delayed jobs
+    pub current_jobs_delayed: u64,
+    /// Number of buried jobs
+    pub current_jobs_buried: u64,
+    /// Cumulative count of jobs created in this tube
+    pub total_jobs: u64,
+    /// Number of open connections currently using this tube
+    pub current_using: u64,
+    /// Number of open connections waiting on a reserve for this tube
+    pub current_waiting: u64,
+    /// Number of open connections watching this tube
+    pub current_watching: u64,
+    /// Time the tube has been paused for
+    pub pause: Duration,
+    /// Cumulative number of delete commands for this tube
+    pub cmd_delete: u64,
+    /// Cumulative number of pause-tube commands for this tube
+    pub cmd_pause_tube: u64,
+    /// Time until the tube is un-paused
+    pub pause_time_left: Duration,
+    /// Fields returned by the server which are not known to this client
+    pub extra: HashMap<String, String>,
+}
+
+impl TubeStats {
+    /// Returns whether reserving jobs from the tube is currently paused
+    pub fn is_paused(&self) -> bool {
+        self.pause_time_left > Duration::from_secs(0)
+    }
+
+    pub(crate) fn from_map(map: HashMap<String, String>,
+                           command: &Command)
+                           -> BeanstalkdResult<TubeStats> {
+        let mut fields = Fields(map, command.line());
+        Ok(TubeStats {
+            name: fields.string("name"),
+            current_jobs_urgent: fields.number("current-jobs-urgent")?,
+            current_jobs_ready: fields.number("current-jobs-ready")?,
+            current_jobs_reserved: fields.number("current-jobs-reserved")?,
+            current_jobs_delayed: fields.number("current-jobs-delayed")?,
+            current_jobs_buried: fields.number("current-jobs-buried")?,
+            total_jobs: fields.number("total-jobs")?,
+            current_using: fields.number("current-using")?,
+            current_waiting: fields.number("current-waiting")?,
+            current_watching: fields.number("current-watching")?,
+            pause: fields.seconds("pause")?,
+            cmd_delete: fields.number("cmd-delete")?,
+            cmd_pause_tube: fields.number("cmd-pause-tube")?,
+            pause_time_left: fields.seconds("pause-time-left")?,
+            extra: fields.0,
+        })
+    }
+}
+
+/// The state a job is in
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum JobState {
+    Ready,
+    Delayed,
+    Reserved,
+    Buried,
+    /// A state not known to this client
+    Other(String),
+}
+
+impl From<&str> for JobState {
+    fn from(state: &str) -> JobState {
+        match state {
+            "ready" => JobState::Ready,
+            "delayed" => JobState::Delayed,
+            "reserved" => JobState::Reserved,
+            "buried" => JobState::Buried,
+            other => JobState::Other(other.to_string()),
+        }
+    }
+}
+
+/// Statistics of a single job as returned by `stats-job`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct JobStats {
+    /// Job id
+    pub id: u64,
+    /// Name of the tube that contains this job
+    pub tube: String,
+    /// Current state of the job
+    pub state: JobState,
+    /// Priority of the job
+    pub pri: u32,
+    /// Time since the put command that created this job
+    pub age: Duration,
+    /// Delay the job was put or released with
+    pub delay: Duration,
+    /// Time to run the job was put with
+    pub ttr: Duration,
+    /// Time until the server puts this job into the ready queue. Only meaningful if the job is
+    /// reserved or delayed.
+    pub time_left: Duration,
+    /// Number of the earliest binlog file containing this job
+    pub file: u64,
+    /// Number of times this job has been reserved
+    pub reserves: u64,
+    /// Number of times this job has timed out during a reservation
+    pub timeouts: u64,
+    /// Number of times a client has released this job from a reservation
+    pub releases: u64,
+    /// Number of times this job has been buried
+    pub buries: u64,
+    /// Number of times this job has been kicked
+    pub kicks: u64,
+    /// Fields returned by the server which are not known to this client
+    pub extra: HashMap<String, String>,
+}
+
+impl JobStats {
+    pub(crate) fn from_map(map: HashMap<String, String>,
+                           command: &Command)
+                           -> BeanstalkdResult<JobStats> {
+        let mut fields = Fields(map, command.line());
+        Ok(JobStats {
+            id: fields.number("id")?,
+            tube: fields.string("tube"),
+            state: JobState::from(fields.string("state").as_str()),
+            pri: fields.number("pri")?,
+            age: fields.seconds("age")?,
+            delay: fields.seconds("delay")?,
+            ttr: fields.seconds("ttr")?,
+            time_left: fields.seconds("time-left")?,
+            file: fields.number("file")?,
+            reserves: fields.number("reserves")?,
+            timeouts: fields.number("timeouts")?,
+            releases: fields.number("releases")?,
+            buries: fields.number("buries")?,
+            kicks: fields.number("kicks")?,
+            extra: fields.0,
+        })
+    }
+}
+
+/// Removes known keys one by one, leaving the unknown ones behind.
+/// Keys missing from the server's reply default to zero or an empty string.
+struct Fields<'a>(HashMap<String, String>, &'a str);
+
+impl<'a> Fields<'a> {
+    /// Returns the value for `key` without surrounding YAML quotes
+    fn string(&mut self, key: &str) -> String {
+        self.0.remove(key).map(|value| value.trim_matches('"').to_string()).unwrap_or_default()
+    }
+
+    fn number<T>(&mut self, key: &str) -> BeanstalkdResult<T>
+        where T: FromStr + Default,
+              T::Err: Error + Send + Sync + 'static
+    {
+        match self.0.remove(key) {
+            Some(value) => value.parse().map_err(|e| self.error(Box::new(e))),
+            None => Ok(T::default()),
+        }
+    }
+
+    fn seconds(&mut self, key: &str) -> BeanstalkdResult<Duration> {
+        self.number(key).map(Duration::from_secs)
+    }
+
+    fn fractional_seconds(&mut self, key: &str) -> BeanstalkdResult<Duration> {
+        let seconds: f64 = self.number(key)?;
+        Duration::try_from_secs_f64(seconds).map_err(|e| self.error(Box::new(e)))
+    }
+
+    fn error(&self, source: Box<dyn Error + Send + Sync>) -> BeanstalkdError {
+        BeanstalkdError::ParseError {
+            command: self.1.to_string(),
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|&(key, value)| (key.to_string(), value.to_string())).collect()
+}
+
+#[test]
+fn server_stats_test() {
+    let fields = map(&[("current-jobs-ready", "3"),
+                       ("version", "\"1.12\""),
+                       ("rusage-utime", "0.148000"),
+                       ("uptime", "42"),
+                       ("draining", "false"),
+                       ("cmd-frobnicate", "7")]);
+    let stats = ServerStats::from_map(fields, &crate::commands::stats()).unwrap();
+    assert_eq!(stats.current_jobs_ready, 3);
+    assert_eq!(stats.version, "1.12");
+    assert_eq!(stats.rusage_utime, Duration::from_millis(148));
+    assert_eq!(stats.uptime, Duration::from_secs(42));
+    assert!(!stats.draining);
+    assert_eq!(stats.extra, map(&[("cmd-frobnicate", "7")]));
+}
+
+#[test]
+fn tube_stats_test() {
+    let fields = map(&[("name", "default"),
+                       ("current-jobs-ready", "3"),
+                       ("current-waiting", "1"),
+                       ("pause", "30"),
+                       ("pause-time-left", "12")]);
+    let stats = TubeStats::from_map(fields, &crate::commands::stats_tube("default")).unwrap();
+    assert_eq!(stats.name, "default");
+    assert_eq!(stats.current_jobs_ready, 3);
+    assert_eq!(stats.current_waiting, 1);
+    assert_eq!(stats.total_jobs, 0);
+    assert_eq!(stats.pause, Duration::from_secs(30));
+    assert_eq!(stats.pause_time_left, Duration::from_secs(12));
+    assert!(stats.is_paused());
+    assert!(stats.extra.is_empty());
+}
+
+#[test]
+fn tube_stats_malformed_test() {
+    let fields = map(&[("current-jobs-ready", "three")]);
+    match TubeStats::from_map(fields, &crate::commands::stats_tube("default")) {
+        Err(BeanstalkdError::ParseError { command, .. }) => {
+            assert_eq!(command, "stats-tube default")
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn job_stats_test() {
+    let fields = map(&[("id", "8"),
+                       ("tube", "default"),
+                       ("state", "buried"),
+                       ("pri", "1024"),
+                       ("age", "12"),
+                       ("ttr", "60"),
+                       ("time-left", "0")]);
+    let stats = JobStats::from_map(fields, &crate::commands::stats_job(8)).unwrap();
+    assert_eq!(stats.id, 8);
+    assert_eq!(stats.tube, "default");
+    assert_eq!(stats.state, JobState::Buried);
+    assert_eq!(stats.pri, 1024);
+    assert_eq!(stats.age, Duration::from_secs(12));
+    assert_eq!(stats.ttr, Duration::from_secs(60));
+}
+
+#[test]
+fn job_state_test() {
+    assert_eq!(JobState::from("ready"), JobState::Ready);
+    assert_eq!(JobState::from("delayed"), JobState::Delayed);
+    assert_eq!(JobState::from("reserved"), JobState::Reserved);
+    assert_eq!(JobState::from("invalid"), JobState::Other("invalid".to_string()));
+}
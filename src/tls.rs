@@ -0,0 +1,120 @@
+use std::convert::TryFrom;
+use std::io;
+use std::net::TcpStream;
+use std::sync::Arc;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+use crate::beanstalkd::Beanstalkd;
+use crate::error::{BeanstalkdError, BeanstalkdResult};
+
+/// A TCP connection secured with TLS
+pub type TlsStream = StreamOwned<ClientConnection, TcpStream>;
+
+/// Which servers to trust and how to authenticate towards them when connecting with TLS
+#[derive(Debug)]
+pub struct TlsConfig {
+    roots: RootCertStore,
+    client_certificate: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+}
+
+impl TlsConfig {
+    /// Trusts the certificate authorities trusted by Mozilla
+    pub fn new() -> TlsConfig {
+        let mut config = TlsConfig::without_roots();
+        config.roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        config
+    }
+
+    /// Trusts no certificate authority at all. Private ones or the self-signed certificate of the
+    /// server have to be added with `add_root_certificates`.
+    pub fn without_roots() -> TlsConfig {
+        TlsConfig {
+            roots: RootCertStore::empty(),
+            client_certificate: None,
+        }
+    }
+
+    /// Trusts all certificates found in the PEM encoded `pem`
+    pub fn add_root_certificates(&mut self, pem: &[u8]) -> BeanstalkdResult<()> {
+        for certificate in CertificateDer::pem_slice_iter(pem) {
+            let certificate = certificate.map_err(|e| BeanstalkdError::TlsError(Box::new(e)))?;
+            self.roots.add(certificate).map_err(|e| BeanstalkdError::TlsError(Box::new(e)))?;
+        }
+        Ok(())
+    }
+
+    /// Authenticates with the PEM encoded certificate chain and private key if the server asks
+    /// for a client certificate
+    pub fn set_client_certificate(&mut self,
+                                  certificate_chain_pem: &[u8],
+                                  private_key_pem: &[u8])
+                                  -> BeanstalkdResult<()> {
+        let chain = CertificateDer::pem_slice_iter(certificate_chain_pem)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| BeanstalkdError::TlsError(Box::new(e)))?;
+        let key = PrivateKeyDer::from_pem_slice(private_key_pem)
+            .map_err(|e| BeanstalkdError::TlsError(Box::new(e)))?;
+        self.client_certificate = Some((chain, key));
+        Ok(())
+    }
+
+    fn client_config(&self) -> BeanstalkdResult<ClientConfig> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| BeanstalkdError::TlsError(Box::new(e)))?
+            .with_root_certificates(self.roots.clone());
+        match &self.client_certificate {
+            Some((chain, key)) => {
+                builder.with_client_auth_cert(chain.clone(), key.clone_key())
+                    .map_err(|e| BeanstalkdError::TlsError(Box::new(e)))
+            }
+            None => Ok(builder.with_no_client_auth()),
+        }
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> TlsConfig {
+        TlsConfig::new()
+    }
+}
+
+impl Beanstalkd<TlsStream> {
+    /// Connect to a beanstalkd server behind TLS, e.g. a TLS terminating proxy. The server's
+    /// certificate has to be valid for `host`.
+    pub fn connect_tls(host: &str,
+                       port: u16,
+                       config: &TlsConfig)
+                       -> BeanstalkdResult<Beanstalkd<TlsStream>> {
+        connect(host, port, config).map(Beanstalkd::from_stream)
+    }
+}
+
+/// Opens a TCP connection and completes the TLS handshake on it
+pub(crate) fn connect(host: &str, port: u16, config: &TlsConfig) -> BeanstalkdResult<TlsStream> {
+    let server_name = ServerName::try_from(host.to_string())
+        .map_err(|e| BeanstalkdError::TlsError(Box::new(e)))?;
+    let connection = ClientConnection::new(Arc::new(config.client_config()?), server_name)
+        .map_err(|e| BeanstalkdError::TlsError(Box::new(e)))?;
+    let tcp_stream = TcpStream::connect((host, port)).map_err(BeanstalkdError::ConnectionError)?;
+
+    let mut stream = StreamOwned::new(connection, tcp_stream);
+    // Handshake right away so certificate problems surface here instead of in the first command
+    while stream.conn.is_handshaking() {
+        stream.conn.complete_io(&mut stream.sock).map_err(handshake_error)?;
+    }
+    Ok(stream)
+}
+
+/// rustls reports failed handshakes as I/O errors wrapping its own error
+fn handshake_error(error: io::Error) -> BeanstalkdError {
+    let is_tls_error = error.get_ref().map(|e| e.is::<rustls::Error>()).unwrap_or(false);
+    if is_tls_error {
+        BeanstalkdError::TlsError(Box::new(error))
+    } else {
+        BeanstalkdError::ConnectionError(error)
+    }
+}
@@ -0,0 +1,267 @@
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use crate::beanstalkd::Beanstalkd;
+use crate::error::{BeanstalkdError, BeanstalkdResult};
+#[cfg(feature = "tls")]
+use crate::tls::{self, TlsConfig, TlsStream};
+
+/// The environment variable read by `Beanstalkd::from_env`
+pub const URL_VARIABLE: &str = "BEANSTALKD_URL";
+
+const DEFAULT_PORT: u16 = 11300;
+
+/// A connection to the server made from a URL, which may use any of the supported transports
+pub enum AnyStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<TlsStream>),
+}
+
+impl Read for AnyStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            AnyStream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            AnyStream::Unix(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            AnyStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for AnyStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            AnyStream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            AnyStream::Unix(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            AnyStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            AnyStream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            AnyStream::Unix(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            AnyStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+impl Beanstalkd<AnyStream> {
+    /// Connects to the server described by `url` and selects its tubes. Supported are
+    ///
+    /// - `beanstalk://host[:port][/tube][?watch=a,b]`
+    /// - `beanstalks://host[:port][/tube][?watch=a,b]`, using TLS (requires the `tls` feature)
+    /// - `beanstalk+unix:///path/to/socket[?tube=tube&watch=a,b]`
+    ///
+    /// The port defaults to 11300. The tube, which can also be given as `tube` parameter, is
+    /// used for new jobs. If tubes to watch are given, they replace the `default` tube unless it
+    /// is one of them.
+    pub fn from_url(url: &str) -> BeanstalkdResult<Beanstalkd<AnyStream>> {
+        let url = ConnectionUrl::parse(url)?;
+        let stream = match &url.address {
+            Address::Tcp(host, port) => {
+                AnyStream::Tcp(TcpStream::connect((host.as_str(), *port))
+                    .map_err(BeanstalkdError::ConnectionError)?)
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                let unix_stream = UnixStream::connect(path)
+                    .map_err(BeanstalkdError::ConnectionError)?;
+                AnyStream::Unix(unix_stream)
+            }
+            #[cfg(not(unix))]
+            Address::Unix(_) => {
+                let message = "Unix domain sockets are not supported on this platform";
+                return Err(BeanstalkdError::InvalidUrl(message.to_string()));
+            }
+            #[cfg(feature = "tls")]
+            Address::Tls(host, port) => {
+                AnyStream::Tls(Box::new(tls::connect(host, *port, &TlsConfig::new())?))
+            }
+            #[cfg(not(feature = "tls"))]
+            Address::Tls(..) => {
+                let message = "`beanstalks` URLs require the `tls` feature";
+                return Err(BeanstalkdError::InvalidUrl(message.to_string()));
+            }
+        };
+
+        let mut beanstalkd = Beanstalkd::from_stream(stream);
+        if let Some(tube) = &url.tube {
+            beanstalkd.tube(tube)?;
+        }
+        for tube in &url.watch {
+            beanstalkd.watch(tube)?;
+        }
+        if !url.watch.is_empty() && !url.watch.iter().any(|tube| tube == "default") {
+            beanstalkd.ignore("default")?;
+        }
+        Ok(beanstalkd)
+    }
+
+    /// Connects to the server given by the `BEANSTALKD_URL` environment variable as described in
+    /// `from_url`, or to `localhost:11300` if it isn't set
+    pub fn from_env() -> BeanstalkdResult<Beanstalkd<AnyStream>> {
+        match env::var(URL_VARIABLE) {
+            Ok(url) => Beanstalkd::from_url(&url),
+            Err(env::VarError::NotPresent) => Beanstalkd::from_url("beanstalk://localhost"),
+            Err(e) => Err(BeanstalkdError::InvalidUrl(format!("{}: {}", URL_VARIABLE, e))),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Address {
+    Tcp(String, u16),
+    Unix(String),
+    Tls(String, u16),
+}
+
+#[derive(Debug, PartialEq)]
+struct ConnectionUrl {
+    address: Address,
+    tube: Option<String>,
+    watch: Vec<String>,
+}
+
+impl ConnectionUrl {
+    fn parse(url: &str) -> BeanstalkdResult<ConnectionUrl> {
+        let invalid = |message: &str| {
+            BeanstalkdError::InvalidUrl(format!("{} in `{}`", message, url))
+        };
+
+        let (scheme, rest) = url.split_once("://").ok_or_else(|| invalid("missing scheme"))?;
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, Some(query)),
+            None => (rest, None),
+        };
+
+        let mut tube = None;
+        let address = match scheme {
+            "beanstalk+unix" => {
+                if rest.is_empty() {
+                    return Err(invalid("missing socket path"));
+                }
+                Address::Unix(rest.to_string())
+            }
+            "beanstalk" | "beanstalks" => {
+                let (authority, path) = match rest.split_once('/') {
+                    Some((authority, path)) => (authority, path),
+                    None => (rest, ""),
+                };
+                if !path.is_empty() {
+                    tube = Some(path.to_string());
+                }
+                let (host, port) = split_host_port(authority)
+                    .ok_or_else(|| invalid("invalid host"))?;
+                if scheme == "beanstalks" {
+                    Address::Tls(host, port)
+                } else {
+                    Address::Tcp(host, port)
+                }
+            }
+            _ => return Err(invalid("unsupported scheme")),
+        };
+
+        let mut watch = Vec::new();
+        for parameter in query.unwrap_or_default().split('&').filter(|p| !p.is_empty()) {
+            match parameter.split_once('=') {
+                Some(("tube", value)) if !value.is_empty() => tube = Some(value.to_string()),
+                Some(("watch", value)) => {
+                    watch.extend(value.split(',').filter(|t| !t.is_empty()).map(|t| t.to_string()))
+                }
+                _ => return Err(invalid(&format!("unsupported parameter `{}`", parameter))),
+            }
+        }
+
+        Ok(ConnectionUrl { address, tube, watch })
+    }
+}
+
+/// Splits `host[:port]`, where the host may be an IPv6 address in brackets
+fn split_host_port(authority: &str) -> Option<(String, u16)> {
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        match rest {
+            "" => (host, None),
+            _ => (host, Some(rest.strip_prefix(':')?)),
+        }
+    } else {
+        match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => DEFAULT_PORT,
+    };
+    Some((host.to_string(), port))
+}
+
+#[test]
+fn parse_tcp_url_test() {
+    let url = ConnectionUrl::parse("beanstalk://queue.local:11400/jobs?watch=a,b").unwrap();
+    assert_eq!(url,
+               ConnectionUrl {
+                   address: Address::Tcp("queue.local".to_string(), 11400),
+                   tube: Some("jobs".to_string()),
+                   watch: vec!["a".to_string(), "b".to_string()],
+               });
+
+    let url = ConnectionUrl::parse("beanstalk://localhost").unwrap();
+    assert_eq!(url.address, Address::Tcp("localhost".to_string(), 11300));
+    assert_eq!(url.tube, None);
+    assert!(url.watch.is_empty());
+}
+
+#[test]
+fn parse_ipv6_url_test() {
+    let url = ConnectionUrl::parse("beanstalk://[::1]:11301/").unwrap();
+    assert_eq!(url.address, Address::Tcp("::1".to_string(), 11301));
+    let url = ConnectionUrl::parse("beanstalks://[::1]").unwrap();
+    assert_eq!(url.address, Address::Tls("::1".to_string(), 11300));
+}
+
+#[test]
+fn parse_unix_url_test() {
+    let url = ConnectionUrl::parse("beanstalk+unix:///run/beanstalkd.sock?tube=jobs&watch=jobs")
+        .unwrap();
+    assert_eq!(url,
+               ConnectionUrl {
+                   address: Address::Unix("/run/beanstalkd.sock".to_string()),
+                   tube: Some("jobs".to_string()),
+                   watch: vec!["jobs".to_string()],
+               });
+}
+
+#[test]
+fn parse_invalid_url_test() {
+    let invalid = ["localhost:11300",
+                   "http://localhost",
+                   "beanstalk://",
+                   "beanstalk://:11300",
+                   "beanstalk://localhost:port",
+                   "beanstalk://localhost:99999",
+                   "beanstalk://[::1",
+                   "beanstalk+unix://",
+                   "beanstalk://localhost?priority=1"];
+    for url in invalid.iter() {
+        assert!(matches!(ConnectionUrl::parse(url), Err(BeanstalkdError::InvalidUrl(_))),
+                "{} was accepted",
+                url);
+    }
+}
@@ -0,0 +1,41 @@
+// Test the async client against a running beanstalkd server
+
+#![cfg(feature = "async")]
+
+extern crate beanstalkd;
+
+use beanstalkd::{AsyncBeanstalkd, ReserveOutcome};
+
+#[tokio::test]
+async fn async_produce_and_consume_message() {
+    let message = "Hello Async World";
+    let mut beanstalkd = AsyncBeanstalkd::localhost().await.unwrap();
+    beanstalkd.tube("async-hello-world").await.unwrap();
+    beanstalkd.put(message, 0, 0, 10000).await.unwrap();
+
+    beanstalkd.watch("async-hello-world").await.unwrap();
+    let (id, body) = beanstalkd.reserve().await.unwrap();
+    assert_eq!(message, body);
+    beanstalkd.delete(id).await.unwrap();
+}
+
+#[tokio::test]
+async fn async_peek_and_delete_all() {
+    let mut beanstalkd = AsyncBeanstalkd::localhost().await.unwrap();
+    beanstalkd.tube("async-peek").await.unwrap();
+    beanstalkd.delete_all().await.unwrap();
+    let id = beanstalkd.put_bytes(&[0xff, 0x00], 0, 0, 10000).await.unwrap();
+
+    assert_eq!(beanstalkd.peek_ready_bytes().await.unwrap(), Some((id, vec![0xff, 0x00])));
+    beanstalkd.delete_all().await.unwrap();
+    assert_eq!(beanstalkd.peek_ready_bytes().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn async_reserve_times_out() {
+    let mut beanstalkd = AsyncBeanstalkd::localhost().await.unwrap();
+    beanstalkd.watch("async-empty").await.unwrap();
+    beanstalkd.ignore("default").await.unwrap();
+    assert_eq!(beanstalkd.reserve_outcome_with_timeout(0).await.unwrap(), ReserveOutcome::TimedOut);
+    assert_eq!(beanstalkd.list_tubes_watched().await.unwrap(), vec!["async-empty".to_string()]);
+}
@@ -0,0 +1,33 @@
+// Test the codec inside tokio-util's framed streams
+
+#![cfg(feature = "codec")]
+
+extern crate beanstalkd;
+
+use futures_util::StreamExt;
+use tokio_util::codec::FramedRead;
+
+use beanstalkd::{BeanstalkdCodec, BeanstalkdError};
+
+#[tokio::test]
+async fn server_errors_dont_end_the_stream() {
+    let input = &b"INSERTED 1\r\nJOB_TOO_BIG\r\nINSERTED 3\r\n"[..];
+    let mut replies = FramedRead::new(input, BeanstalkdCodec::new());
+
+    let reply = replies.next().await.unwrap().unwrap().unwrap();
+    assert_eq!(reply.arguments, vec!["1".to_string()]);
+    assert!(matches!(replies.next().await, Some(Ok(Err(BeanstalkdError::JobTooBig)))));
+    let reply = replies.next().await.unwrap().unwrap().unwrap();
+    assert_eq!(reply.arguments, vec!["3".to_string()]);
+    assert!(replies.next().await.is_none());
+}
+
+#[tokio::test]
+async fn malformed_replies_end_the_stream() {
+    let input = &b"INSERTED 1\r\nRESERVED 2 x\r\nINSERTED 3\r\n"[..];
+    let mut replies = FramedRead::new(input, BeanstalkdCodec::new());
+
+    assert!(replies.next().await.unwrap().unwrap().is_ok());
+    assert!(matches!(replies.next().await, Some(Err(BeanstalkdError::ParseError { .. }))));
+    assert!(replies.next().await.is_none());
+}
@@ -0,0 +1,78 @@
+// A fake beanstalkd server for the tests which check what goes over the wire
+
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Decides the reply to each command line. Returning `None` closes the connection instead.
+pub type Answer = Box<dyn FnMut(&str) -> Option<Vec<u8>> + Send>;
+
+/// Answers each command with the next of `replies` and closes the connection at the first
+/// command after they ran out. An empty reply answers nothing, e.g. to receive a whole pipelined
+/// batch before replying.
+pub fn replies(replies: &[&str]) -> Answer {
+    let mut replies: VecDeque<Vec<u8>> =
+        replies.iter().map(|reply| reply.as_bytes().to_vec()).collect();
+    Box::new(move |_| replies.pop_front())
+}
+
+/// Reads the commands sent over `stream`, skipping the body of every `put`, and writes the
+/// answer to each. Returns once the client closes the connection or `answer` returns `None`.
+pub fn serve<S: Read + Write>(stream: S, answer: &mut Answer) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        if line.starts_with("put ") {
+            let bytes: usize = line.trim_end().rsplit(' ').next().unwrap().parse().unwrap();
+            let mut body = vec![0; bytes + 2];
+            reader.read_exact(&mut body).unwrap();
+        }
+        let reply = match answer(line.trim_end()) {
+            Some(reply) => reply,
+            None => return,
+        };
+        let stream = reader.get_mut();
+        if stream.write_all(&reply).and_then(|_| stream.flush()).is_err() {
+            return;
+        }
+    }
+}
+
+/// A fake server listening on a local TCP port
+pub struct FakeServer {
+    pub port: u16,
+    /// The line of every command received, in order
+    pub commands: Receiver<String>,
+}
+
+/// Starts a server which accepts one connection for each of `answers` and serves it with that
+/// answer. It stops listening once the last connection is accepted, so connecting again fails.
+pub fn tcp_server(answers: Vec<Answer>) -> FakeServer {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (sender, commands) = mpsc::channel();
+    thread::spawn(move || {
+        let count = answers.len();
+        let mut listener = Some(listener);
+        for (index, mut answer) in answers.into_iter().enumerate() {
+            let (stream, _) = listener.as_ref().unwrap().accept().unwrap();
+            if index + 1 == count {
+                listener = None;
+            }
+            let sender = sender.clone();
+            let mut answer: Answer = Box::new(move |line| {
+                let _ = sender.send(line.to_string());
+                answer(line)
+            });
+            serve(stream, &mut answer);
+        }
+    });
+    FakeServer { port, commands }
+}
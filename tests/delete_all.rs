@@ -22,7 +22,7 @@ fn delete_all_ready() {
 
     // There shouldn't be anything left in the tube
     let result = beanstalkd.peek_ready();
-    assert_eq!(result, Ok(None));
+    assert_eq!(result.unwrap(), None);
 }
 
 #[test]
@@ -46,7 +46,7 @@ fn delete_all_delayed() {
 
     // There shouldn't be anything left in the tube
     let result = beanstalkd.peek_delayed();
-    assert_eq!(result, Ok(None));
+    assert_eq!(result.unwrap(), None);
 }
 
 #[test]
@@ -70,5 +70,5 @@ fn delete_all_buried() {
 
     // There shouldn't be anything left in the tube
     let result = beanstalkd.peek_buried();
-    assert_eq!(result, Ok(None));
+    assert_eq!(result.unwrap(), None);
 }
\ No newline at end of file
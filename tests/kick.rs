@@ -0,0 +1,51 @@
+// Test the kick functionality
+
+extern crate beanstalkd;
+
+use beanstalkd::Beanstalkd;
+
+#[test]
+fn kick_moves_buried_jobs_to_ready() {
+    let tube_name = "kick_moves_buried_jobs_to_ready";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube(tube_name).unwrap();
+    beanstalkd.watch(tube_name).unwrap();
+    for idx in 0..3 {
+        let message = format!("Message {}", idx);
+        beanstalkd.put(&message, 0, 0, 10000).unwrap();
+        let (job_id, _) = beanstalkd.reserve().unwrap();
+        beanstalkd.bury(job_id, 0).unwrap();
+    }
+
+    assert_eq!(beanstalkd.kick(2).unwrap(), 2);
+    assert_eq!(beanstalkd.kick(10).unwrap(), 1);
+    assert_eq!(beanstalkd.kick(10).unwrap(), 0);
+
+    // Clean up
+    beanstalkd.delete_all().unwrap();
+}
+
+#[test]
+fn kick_job_moves_a_single_job_to_ready() {
+    let tube_name = "kick_job_moves_a_single_job_to_ready";
+    let message = "Hello World";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube(tube_name).unwrap();
+    beanstalkd.put(message, 0, 0, 10000).unwrap();
+
+    beanstalkd.watch(tube_name).unwrap();
+    let (job_id, _) = beanstalkd.reserve().unwrap();
+    beanstalkd.bury(job_id, 0).unwrap();
+
+    assert!(beanstalkd.kick_job(job_id).unwrap());
+    let (ready_id, actual_message) = beanstalkd.peek_ready().unwrap().unwrap();
+    assert_eq!(ready_id, job_id);
+    assert_eq!(actual_message, message);
+
+    // A ready job can't be kicked
+    assert!(!beanstalkd.kick_job(job_id).unwrap());
+
+    // Clean up
+    beanstalkd.delete(job_id).unwrap();
+    assert!(!beanstalkd.kick_job(job_id).unwrap());
+}
@@ -2,7 +2,7 @@
 
 extern crate beanstalkd;
 
-use beanstalkd::Beanstalkd;
+use beanstalkd::{Beanstalkd, ReserveOutcome};
 
 #[test]
 fn produce_and_consume_simple_message() {
@@ -17,6 +17,83 @@ fn produce_and_consume_simple_message() {
     let _ = beanstalkd.delete(id);
 }
 
+#[test]
+fn produce_and_consume_message_with_surrounding_whitespace() {
+    let message = "\n  {\"id\": 1}\r\n{\"id\": 2}\n";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube("surrounding-whitespace").unwrap();
+    let _ = beanstalkd.put(message, 0, 0, 10000);
+
+    beanstalkd.watch("surrounding-whitespace").unwrap();
+    let (id, body) = beanstalkd.reserve().unwrap();
+    assert_eq!(message, body);
+    let _ = beanstalkd.delete(id);
+}
+
+#[test]
+fn produce_and_consume_binary_message() {
+    let message: &[u8] = &[0xff, 0x00, 0xfe, b'\r', b'\n', 0x80];
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube("binary").unwrap();
+    let id = beanstalkd.put_bytes(message, 0, 0, 10000).unwrap();
+
+    let (_, body) = beanstalkd.peek_bytes(id).unwrap().unwrap();
+    assert_eq!(message, &body[..]);
+    assert!(beanstalkd.peek(id).is_err());
+
+    beanstalkd.watch("binary").unwrap();
+    let (id, body) = beanstalkd.reserve_bytes().unwrap();
+    assert_eq!(message, &body[..]);
+    let _ = beanstalkd.delete(id);
+}
+
+#[test]
+fn reserve_outcome_reports_deadline_soon() {
+    let tube_name = "reserve-outcome-deadline-soon";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube(tube_name).unwrap();
+    beanstalkd.watch(tube_name).unwrap();
+    beanstalkd.ignore("default").unwrap();
+
+    // With a TTR of one second the job enters the server's safety margin right away
+    beanstalkd.put("Hello World", 0, 0, 1).unwrap();
+    let id = match beanstalkd.reserve_outcome().unwrap() {
+        ReserveOutcome::Job(id, body) => {
+            assert_eq!(body, "Hello World");
+            id
+        }
+        other => panic!("expected a job, got {:?}", other),
+    };
+    assert_eq!(beanstalkd.reserve_outcome_with_timeout(1).unwrap(),
+               ReserveOutcome::DeadlineSoon);
+
+    // Clean up
+    beanstalkd.delete(id).unwrap();
+    assert_eq!(beanstalkd.reserve_outcome_with_timeout(0).unwrap(), ReserveOutcome::TimedOut);
+}
+
+#[test]
+fn reserve_job_by_id() {
+    let tube_name = "reserve-job-by-id";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube(tube_name).unwrap();
+    let first_id = beanstalkd.put("First", 0, 0, 10000).unwrap();
+    let second_id = beanstalkd.put("Second", 0, 0, 10000).unwrap();
+
+    // The job is reserved even though it isn't next in line and its tube isn't watched
+    let (id, body) = beanstalkd.reserve_job(second_id).unwrap().unwrap();
+    assert_eq!(id, second_id);
+    assert_eq!(body, "Second");
+
+    // An already reserved job can't be reserved again
+    assert_eq!(beanstalkd.reserve_job(second_id).unwrap(), None);
+
+    // Clean up
+    beanstalkd.delete(first_id).unwrap();
+    beanstalkd.delete(second_id).unwrap();
+    assert_eq!(beanstalkd.reserve_job(second_id).unwrap(), None);
+}
+
 #[test]
 fn handle_envelope_signed_by_investor() {
     let message = include_str!("../data/signed-by-investor.json");
@@ -30,7 +107,7 @@ fn handle_envelope_signed_by_investor() {
     assert_eq!(message, body);
 
     let result = beanstalkd.delete(id);
-    assert_eq!(result.is_ok(), true)
+    assert!(result.is_ok())
 }
 
 #[test]
@@ -48,7 +125,7 @@ fn handle_envelope_signed_by_investor_in_loop() {
         assert_eq!(message, body);
 
         let result = beanstalkd.delete(id);
-        assert_eq!(result.is_ok(), true)
+        assert!(result.is_ok())
     }
 }
 
@@ -75,5 +152,5 @@ fn handle_large_message() {
     assert_eq!(message, body);
 
     let result = beanstalkd.delete(id);
-    assert_eq!(result.is_ok(), true)
+    assert!(result.is_ok())
 }
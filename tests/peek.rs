@@ -16,7 +16,25 @@ fn no_peek_on_empty_tube() {
 
     beanstalkd.watch(tube_name).unwrap();
     let result = beanstalkd.peek_ready();
-    assert_eq!(result, Ok(None));
+    assert_eq!(result.unwrap(), None);
+}
+
+#[test]
+fn peek_finds_a_message_by_id() {
+    let tube_name = "peek_finds_a_message_by_id";
+    let message = "Hello World";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube(tube_name).unwrap();
+    let job_id = beanstalkd.put(message, 0, 0, 10000).unwrap();
+
+    let (actual_id, actual_message) = beanstalkd.peek(job_id).unwrap().unwrap();
+    assert_eq!(actual_id, job_id);
+    assert_eq!(actual_message, message);
+
+    // Clean up
+    beanstalkd.delete(job_id).unwrap();
+    let result = beanstalkd.peek(job_id);
+    assert_eq!(result.unwrap(), None);
 }
 
 #[test]
@@ -55,7 +73,7 @@ fn peek_ready_ignores_delayed_job() {
     // Because we're using peek_ready, it won't catch a job in the delayed state
     beanstalkd.watch(tube_name).unwrap();
     let result = beanstalkd.peek_ready();
-    assert_eq!(result, Ok(None));
+    assert_eq!(result.unwrap(), None);
 
     // Clean up
     beanstalkd.delete(job_id).unwrap();
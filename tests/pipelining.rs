@@ -0,0 +1,69 @@
+// Test pipelined batches of commands
+
+extern crate beanstalkd;
+
+use beanstalkd::{Beanstalkd, BeanstalkdError};
+
+mod common;
+use common::{replies, tcp_server};
+
+#[test]
+fn put_many_pipelines_commands() {
+    // Only answering the last command fails any client waiting for a reply before the next one
+    let answer = replies(&["", "", "INSERTED 1\r\nJOB_TOO_BIG\r\nINSERTED 3\r\n"]);
+    let server = tcp_server(vec![answer]);
+    let mut beanstalkd = Beanstalkd::connect("127.0.0.1", server.port).unwrap();
+
+    let results = beanstalkd.put_many(&["a", "bb", "c"], 0, 0, 10).unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap(), &1);
+    assert!(matches!(results[1], Err(BeanstalkdError::JobTooBig)));
+    assert_eq!(results[2].as_ref().unwrap(), &3);
+
+    let commands: Vec<String> = server.commands.iter().take(3).collect();
+    assert_eq!(commands, vec!["put 0 0 10 1", "put 0 0 10 2", "put 0 0 10 1"]);
+}
+
+#[test]
+fn delete_many_pipelines_commands() {
+    let server = tcp_server(vec![replies(&["", "DELETED\r\nNOT_FOUND\r\n"])]);
+    let mut beanstalkd = Beanstalkd::connect("127.0.0.1", server.port).unwrap();
+
+    let results: Vec<bool> = beanstalkd.delete_many(&[1, 2])
+        .unwrap()
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect();
+    assert_eq!(results, vec![true, false]);
+}
+
+#[test]
+fn broken_connection_keeps_received_replies() {
+    // The server closes the connection instead of answering the second command
+    let server = tcp_server(vec![replies(&["DELETED\r\n"])]);
+    let mut beanstalkd = Beanstalkd::connect("127.0.0.1", server.port).unwrap();
+
+    let results = beanstalkd.delete_many(&[1, 2]).unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(results[0].as_ref().unwrap());
+    assert!(matches!(results[1], Err(BeanstalkdError::ConnectionError(_))));
+}
+
+#[test]
+fn put_and_delete_many_jobs() {
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube("pipelining").unwrap();
+    let bodies: Vec<String> = (0..1000).map(|i| format!("job {}", i)).collect();
+
+    let ids: Vec<u64> = beanstalkd.put_many(&bodies, 0, 0, 10000)
+        .unwrap()
+        .into_iter()
+        .map(|id| id.unwrap())
+        .collect();
+    assert_eq!(ids.len(), 1000);
+    assert_eq!(beanstalkd.peek(ids[999]).unwrap(), Some((ids[999], "job 999".to_string())));
+
+    let results = beanstalkd.delete_many(&ids).unwrap();
+    assert!(results.into_iter().all(|result| result.unwrap()));
+    assert_eq!(beanstalkd.peek(ids[0]).unwrap(), None);
+}
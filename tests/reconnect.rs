@@ -0,0 +1,132 @@
+// Test reconnecting after the connection was lost
+
+extern crate beanstalkd;
+
+mod common;
+
+use std::time::Duration;
+
+use beanstalkd::{Beanstalkd, BeanstalkdError, ConnectOptions, ReconnectPolicy};
+
+use common::{replies, tcp_server};
+
+#[test]
+fn reconnect_restores_tubes() {
+    let server = tcp_server(vec![replies(&["USING jobs\r\n", "WATCHING 2\r\n", "WATCHING 1\r\n"]),
+                                 replies(&["USING jobs\r\n",
+                                           "WATCHING 2\r\n",
+                                           "WATCHING 1\r\n",
+                                           "USING jobs\r\n"])]);
+    let mut beanstalkd = ConnectOptions::new()
+        .reconnect(ReconnectPolicy::new().initial_backoff(Duration::from_millis(10)))
+        .connect("127.0.0.1", server.port)
+        .unwrap();
+
+    beanstalkd.tube("jobs").unwrap();
+    beanstalkd.watch("emails").unwrap();
+    beanstalkd.ignore("default").unwrap();
+    assert_eq!(beanstalkd.list_tube_used().unwrap(), "jobs");
+
+    let commands: Vec<String> = server.commands.iter().take(8).collect();
+    assert_eq!(commands,
+               vec!["use jobs",
+                    "watch emails",
+                    "ignore default",
+                    "list-tube-used",
+                    "use jobs",
+                    "watch emails",
+                    "ignore default",
+                    "list-tube-used"]);
+}
+
+#[test]
+fn reconnect_does_not_repeat_put() {
+    // The server may have inserted the job before the connection was lost
+    let server = tcp_server(vec![replies(&[]), replies(&["INSERTED 6\r\n"])]);
+    let mut beanstalkd = ConnectOptions::new()
+        .reconnect(ReconnectPolicy::new().initial_backoff(Duration::from_millis(10)))
+        .connect("127.0.0.1", server.port)
+        .unwrap();
+
+    assert!(beanstalkd.put("hello", 0, 0, 60).is_err());
+    // The connection was replaced nonetheless
+    assert_eq!(beanstalkd.put("again", 0, 0, 60).unwrap(), 6);
+
+    let commands: Vec<String> = server.commands.iter().take(2).collect();
+    assert_eq!(commands, vec!["put 0 0 60 5", "put 0 0 60 5"]);
+}
+
+#[test]
+fn reconnect_does_not_repeat_unanswered_batch_commands() {
+    let server = tcp_server(vec![replies(&["USING jobs\r\n", "INSERTED 1\r\n"]),
+                                 replies(&["USING jobs\r\n", "INSERTED 4\r\n"])]);
+    let mut beanstalkd = ConnectOptions::new()
+        .reconnect(ReconnectPolicy::new().initial_backoff(Duration::from_millis(10)))
+        .connect("127.0.0.1", server.port)
+        .unwrap();
+
+    beanstalkd.tube("jobs").unwrap();
+    let results = beanstalkd.put_many(&["a", "b", "c"], 0, 0, 60).unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap(), &1);
+    assert!(results[1].is_err());
+    assert!(results[2].is_err());
+    assert_eq!(beanstalkd.put("d", 0, 0, 60).unwrap(), 4);
+
+    let commands: Vec<String> = server.commands.iter().take(5).collect();
+    assert_eq!(commands,
+               vec!["use jobs", "put 0 0 60 1", "put 0 0 60 1", "use jobs", "put 0 0 60 1"]);
+}
+
+#[test]
+fn reconnect_gives_up_after_max_attempts() {
+    let server = tcp_server(vec![replies(&["USING default\r\n"])]);
+    let mut beanstalkd = ConnectOptions::new()
+        .reconnect(ReconnectPolicy::new()
+            .max_attempts(2)
+            .initial_backoff(Duration::from_millis(10)))
+        .connect("127.0.0.1", server.port)
+        .unwrap();
+
+    beanstalkd.tube("default").unwrap();
+    assert!(matches!(beanstalkd.stats(), Err(BeanstalkdError::ConnectionError(_))));
+}
+
+#[test]
+fn no_reconnect_by_default() {
+    let server = tcp_server(vec![replies(&[]), replies(&["USING default\r\n"])]);
+    let mut beanstalkd = Beanstalkd::connect("127.0.0.1", server.port).unwrap();
+
+    assert!(matches!(beanstalkd.tube("default"), Err(BeanstalkdError::ConnectionError(_))));
+}
+
+#[test]
+fn malformed_reply_breaks_connection() {
+    // The byte count is not a number, so the body stays unread
+    let server = tcp_server(vec![replies(&["RESERVED 5 x\r\nhello\r\n", "USING jobs\r\n"])]);
+    let mut beanstalkd = Beanstalkd::connect("127.0.0.1", server.port).unwrap();
+
+    assert!(matches!(beanstalkd.reserve(), Err(BeanstalkdError::ParseError { .. })));
+    assert!(matches!(beanstalkd.tube("jobs"), Err(BeanstalkdError::ConnectionError(_))));
+}
+
+#[test]
+fn reconnect_after_malformed_reply() {
+    let server = tcp_server(vec![replies(&["RESERVED 5 x\r\nhello\r\n"]),
+                                 replies(&["RESERVED 5 5\r\nhello\r\n"])]);
+    let mut beanstalkd = ConnectOptions::new()
+        .reconnect(ReconnectPolicy::new().initial_backoff(Duration::from_millis(10)))
+        .connect("127.0.0.1", server.port)
+        .unwrap();
+
+    assert_eq!(beanstalkd.reserve().unwrap(), (5, "hello".to_string()));
+
+    let commands: Vec<String> = server.commands.iter().take(2).collect();
+    assert_eq!(commands, vec!["reserve", "reserve"]);
+}
+
+#[test]
+fn client_stays_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<Beanstalkd>();
+}
@@ -0,0 +1,86 @@
+// Test the stats functionality
+
+extern crate beanstalkd;
+
+use std::time::Duration;
+
+use beanstalkd::{Beanstalkd, JobState};
+
+#[test]
+fn stats_reports_server_info() {
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    let stats = beanstalkd.stats().unwrap();
+    assert!(!stats.version.is_empty());
+    assert!(stats.current_connections >= 1);
+    assert!(stats.max_job_size > 0);
+}
+
+#[test]
+fn stats_job_reports_job_state() {
+    let tube_name = "stats_job_reports_job_state";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube(tube_name).unwrap();
+    let job_id = beanstalkd.put("Hello World", 5, 0, 120).unwrap();
+
+    let stats = beanstalkd.stats_job(job_id).unwrap().unwrap();
+    assert_eq!(stats.id, job_id);
+    assert_eq!(stats.tube, tube_name);
+    assert_eq!(stats.state, JobState::Ready);
+    assert_eq!(stats.pri, 5);
+    assert_eq!(stats.ttr, Duration::from_secs(120));
+
+    // Clean up
+    beanstalkd.delete(job_id).unwrap();
+    assert_eq!(beanstalkd.stats_job(job_id).unwrap(), None);
+}
+
+#[test]
+fn stats_tube_counts_jobs() {
+    let tube_name = "stats_tube_counts_jobs";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube(tube_name).unwrap();
+    beanstalkd.delete_all().unwrap();
+    for idx in 0..3 {
+        let message = format!("Message {}", idx);
+        beanstalkd.put(&message, 0, 0, 10000).unwrap();
+    }
+
+    let stats = beanstalkd.stats_tube(tube_name).unwrap().unwrap();
+    assert_eq!(stats.name, tube_name);
+    assert_eq!(stats.current_jobs_ready, 3);
+    assert_eq!(stats.current_jobs_urgent, 3);
+    assert_eq!(stats.current_using, 1);
+
+    // Clean up
+    beanstalkd.delete_all().unwrap();
+}
+
+#[test]
+fn stats_tube_of_unknown_tube() {
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    assert_eq!(beanstalkd.stats_tube("stats_tube_of_unknown_tube").unwrap(), None);
+}
+
+#[test]
+fn pause_tube_is_reported_by_stats_tube() {
+    let tube_name = "pause_tube_is_reported_by_stats_tube";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube(tube_name).unwrap();
+
+    assert!(beanstalkd.pause_tube(tube_name, Duration::from_secs(60)).unwrap());
+    let stats = beanstalkd.stats_tube(tube_name).unwrap().unwrap();
+    assert!(stats.is_paused());
+    assert_eq!(stats.pause, Duration::from_secs(60));
+    assert!(stats.pause_time_left <= Duration::from_secs(60));
+
+    assert!(beanstalkd.resume_tube(tube_name).unwrap());
+    let stats = beanstalkd.stats_tube(tube_name).unwrap().unwrap();
+    assert!(!stats.is_paused());
+}
+
+#[test]
+fn pause_unknown_tube() {
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    let delay = Duration::from_secs(60);
+    assert!(!beanstalkd.pause_tube("pause_unknown_tube", delay).unwrap());
+}
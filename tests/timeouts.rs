@@ -0,0 +1,95 @@
+// Test the connection options against fake servers
+
+extern crate beanstalkd;
+
+mod common;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use beanstalkd::{BeanstalkdError, ConnectOptions};
+
+use common::{tcp_server, Answer};
+
+/// Answers each command after the given delay with the given reply, or never if the reply is
+/// `None`
+fn delayed(replies: Vec<(Duration, Option<&'static str>)>) -> Answer {
+    let mut replies = replies.into_iter();
+    Box::new(move |_| {
+        let (delay, reply) = replies.next()?;
+        thread::sleep(delay);
+        if reply.is_none() {
+            thread::sleep(Duration::from_secs(10));
+        }
+        reply.map(|reply| reply.as_bytes().to_vec())
+    })
+}
+
+#[test]
+fn io_timeout_fails_stalled_command() {
+    let server = tcp_server(vec![delayed(vec![(Duration::from_secs(0), None)])]);
+    let mut beanstalkd = ConnectOptions::new()
+        .io_timeout(Duration::from_millis(200))
+        .connect("127.0.0.1", server.port)
+        .unwrap();
+
+    let start = Instant::now();
+    assert!(matches!(beanstalkd.stats(), Err(BeanstalkdError::RequestError { .. })));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn io_timeout_excludes_reserve_wait() {
+    let replies = vec![(Duration::from_millis(600), Some("TIMED_OUT\r\n")),
+                       (Duration::from_millis(600), Some("USING default\r\n"))];
+    let server = tcp_server(vec![delayed(replies)]);
+    let mut beanstalkd = ConnectOptions::new()
+        .io_timeout(Duration::from_millis(200))
+        .nodelay(true)
+        .keepalive(Duration::from_secs(60))
+        .connect("127.0.0.1", server.port)
+        .unwrap();
+
+    assert_eq!(beanstalkd.reserve_with_timeout(1).unwrap(), None);
+    // Other commands are held to the I/O timeout again
+    assert!(beanstalkd.tube("default").is_err());
+}
+
+#[test]
+fn io_timeout_breaks_connection() {
+    let replies = vec![(Duration::from_millis(400), Some("USING jobs\r\n"))];
+    let server = tcp_server(vec![delayed(replies)]);
+    let mut beanstalkd = ConnectOptions::new()
+        .io_timeout(Duration::from_millis(200))
+        .connect("127.0.0.1", server.port)
+        .unwrap();
+
+    assert!(matches!(beanstalkd.tube("jobs"), Err(BeanstalkdError::RequestError { .. })));
+    // The late reply must not be taken for the reply to the next command
+    thread::sleep(Duration::from_millis(400));
+    assert!(matches!(beanstalkd.tube("other"), Err(BeanstalkdError::ConnectionError(_))));
+}
+
+#[test]
+fn io_timeout_with_longest_reserve_wait() {
+    let replies = vec![(Duration::from_secs(0), Some("TIMED_OUT\r\n"))];
+    let server = tcp_server(vec![delayed(replies)]);
+    let mut beanstalkd = ConnectOptions::new()
+        .io_timeout(Duration::from_millis(200))
+        .connect("127.0.0.1", server.port)
+        .unwrap();
+
+    assert_eq!(beanstalkd.reserve_with_timeout(u64::MAX).unwrap(), None);
+}
+
+#[test]
+fn options_without_timeouts() {
+    let replies = vec![(Duration::from_millis(300), Some("USING default\r\n"))];
+    let server = tcp_server(vec![delayed(replies)]);
+    let mut beanstalkd = ConnectOptions::new()
+        .connect_timeout(Duration::from_secs(1))
+        .connect("127.0.0.1", server.port)
+        .unwrap();
+
+    beanstalkd.tube("default").unwrap();
+}
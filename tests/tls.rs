@@ -0,0 +1,99 @@
+// Test connections secured with TLS against a fake server with a self-signed certificate
+
+#![cfg(feature = "tls")]
+
+extern crate beanstalkd;
+
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+
+use rcgen::CertifiedKey;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+
+use beanstalkd::{Beanstalkd, BeanstalkdError, TlsConfig};
+
+mod common;
+use common::{serve, Answer};
+
+fn self_signed(name: &str) -> CertifiedKey {
+    rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap()
+}
+
+/// Starts a TLS server which answers every command with `USING test`. If `client` is given the
+/// server only accepts clients authenticating with that certificate.
+fn fake_server(server: &CertifiedKey, client: Option<&CertifiedKey>) -> u16 {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .unwrap();
+    let builder = match client {
+        Some(client) => {
+            let mut roots = RootCertStore::empty();
+            roots.add(client.cert.der().clone()).unwrap();
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .unwrap();
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let key = PrivatePkcs8KeyDer::from(server.key_pair.serialize_der());
+    let config = builder.with_single_cert(vec![CertificateDer::from(server.cert.der().to_vec())],
+                                          PrivateKeyDer::Pkcs8(key))
+        .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (tcp_stream, _) = listener.accept().unwrap();
+        let connection = ServerConnection::new(Arc::new(config)).unwrap();
+        let mut answer: Answer = Box::new(|_| Some(b"USING test\r\n".to_vec()));
+        serve(StreamOwned::new(connection, tcp_stream), &mut answer);
+    });
+    port
+}
+
+#[test]
+fn connect_with_custom_root() {
+    let server = self_signed("localhost");
+    let port = fake_server(&server, None);
+
+    let mut config = TlsConfig::without_roots();
+    config.add_root_certificates(server.cert.pem().as_bytes()).unwrap();
+    let mut beanstalkd = Beanstalkd::connect_tls("localhost", port, &config).unwrap();
+    beanstalkd.tube("test").unwrap();
+    assert_eq!(beanstalkd.list_tube_used().unwrap(), "test");
+}
+
+#[test]
+fn reject_untrusted_certificate() {
+    let server = self_signed("localhost");
+    let port = fake_server(&server, None);
+
+    let result = Beanstalkd::connect_tls("localhost", port, &TlsConfig::new());
+    assert!(matches!(result, Err(BeanstalkdError::TlsError(_))));
+}
+
+#[test]
+fn connect_with_client_certificate() {
+    let server = self_signed("localhost");
+    let client = self_signed("client");
+    let port = fake_server(&server, Some(&client));
+
+    let mut config = TlsConfig::without_roots();
+    config.add_root_certificates(server.cert.pem().as_bytes()).unwrap();
+    config.set_client_certificate(client.cert.pem().as_bytes(),
+                                client.key_pair.serialize_pem().as_bytes())
+        .unwrap();
+    let mut beanstalkd = Beanstalkd::connect_tls("localhost", port, &config).unwrap();
+    beanstalkd.tube("test").unwrap();
+}
+
+#[test]
+fn invalid_pem() {
+    let mut config = TlsConfig::without_roots();
+    assert!(config.set_client_certificate(b"", b"no key").is_err());
+}
@@ -0,0 +1,121 @@
+// Test the clients over streams other than TCP connections
+
+extern crate beanstalkd;
+
+use std::cell::RefCell;
+use std::io::{self, Cursor, Read, Write};
+use std::rc::Rc;
+
+use beanstalkd::{Beanstalkd, BeanstalkdError};
+
+/// An in-memory connection which replies with canned bytes and records everything written
+struct Pipe {
+    replies: Cursor<Vec<u8>>,
+    written: Rc<RefCell<Vec<u8>>>,
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.replies.read(buf)
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn commands_over_in_memory_stream() {
+    let written = Rc::new(RefCell::new(Vec::new()));
+    let pipe = Pipe {
+        replies: Cursor::new(b"USING jobs\r\nINSERTED 7\r\nRESERVED 7 5\r\nhello\r\n".to_vec()),
+        written: written.clone(),
+    };
+    let mut beanstalkd = Beanstalkd::from_stream(pipe);
+
+    beanstalkd.tube("jobs").unwrap();
+    assert_eq!(beanstalkd.put("hello", 1, 0, 60).unwrap(), 7);
+    assert_eq!(beanstalkd.reserve().unwrap(), (7, "hello".to_string()));
+    assert_eq!(written.borrow().as_slice(),
+               &b"use jobs\r\nput 1 0 60 5\r\nhello\r\nreserve\r\n"[..]);
+
+    // The canned replies are used up, like a connection closed by the server
+    assert!(beanstalkd.delete(7).is_err());
+}
+
+#[test]
+fn errors_name_the_command_sent() {
+    let pipe = Pipe {
+        replies: Cursor::new(b"FOUND 7 1\r\n\xff\r\nINSERTED x3\r\n".to_vec()),
+        written: Rc::new(RefCell::new(Vec::new())),
+    };
+    let mut beanstalkd = Beanstalkd::from_stream(pipe);
+
+    match beanstalkd.peek(7) {
+        Err(BeanstalkdError::Utf8Error { command, .. }) => assert_eq!(command, "peek 7"),
+        other => panic!("unexpected result {:?}", other),
+    }
+    match beanstalkd.put("a", 0, 0, 60) {
+        Err(BeanstalkdError::ParseError { command, .. }) => assert_eq!(command, "put 0 0 60 1"),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn job_commands_report_missing_jobs() {
+    let replies = b"DELETED\r\nNOT_FOUND\r\nNOT_FOUND\r\nNOT_FOUND\r\nNOT_FOUND\r\n";
+    let pipe = Pipe {
+        replies: Cursor::new(replies.to_vec()),
+        written: Rc::new(RefCell::new(Vec::new())),
+    };
+    let mut beanstalkd = Beanstalkd::from_stream(pipe);
+
+    assert!(beanstalkd.delete(1).unwrap());
+    assert!(!beanstalkd.delete(2).unwrap());
+    assert!(!beanstalkd.release(2, 0, 0).unwrap());
+    assert!(!beanstalkd.bury(2, 0).unwrap());
+    assert!(!beanstalkd.touch(2).unwrap());
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_commands_over_in_memory_stream() {
+    use beanstalkd::AsyncBeanstalkd;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (client, mut server) = tokio::io::duplex(64);
+    let server = tokio::spawn(async move {
+        let mut request = [0; 21];
+        server.read_exact(&mut request).await.unwrap();
+        server.write_all(b"INSERTED 3\r\n").await.unwrap();
+        request
+    });
+    let mut beanstalkd = AsyncBeanstalkd::from_stream(client);
+
+    assert_eq!(beanstalkd.put("hello", 1, 0, 60).await.unwrap(), 3);
+    assert_eq!(&server.await.unwrap(), b"put 1 0 60 5\r\nhello\r\n");
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_cancelled_command_breaks_connection() {
+    use beanstalkd::AsyncBeanstalkd;
+    use futures_util::FutureExt;
+
+    // The server never replies, so the reserve is cancelled while waiting for its reply
+    let (client, _server) = tokio::io::duplex(64);
+    let mut beanstalkd = AsyncBeanstalkd::from_stream(client);
+
+    assert!(beanstalkd.reserve().now_or_never().is_none());
+    match beanstalkd.tube("jobs").await {
+        Err(BeanstalkdError::ConnectionError(_)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
@@ -0,0 +1,38 @@
+// Test the tube introspection functionality
+
+extern crate beanstalkd;
+
+use beanstalkd::Beanstalkd;
+
+#[test]
+fn list_tube_used_returns_current_tube() {
+    let tube_name = "list_tube_used_returns_current_tube";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    assert_eq!(beanstalkd.list_tube_used().unwrap(), "default".to_string());
+
+    beanstalkd.tube(tube_name).unwrap();
+    assert_eq!(beanstalkd.list_tube_used().unwrap(), tube_name.to_string());
+}
+
+#[test]
+fn list_tubes_watched_returns_watch_list() {
+    let tube_name = "list_tubes_watched_returns_watch_list";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.watch(tube_name).unwrap();
+    assert_eq!(beanstalkd.list_tubes_watched().unwrap(),
+               vec!["default".to_string(), tube_name.to_string()]);
+
+    beanstalkd.ignore("default").unwrap();
+    assert_eq!(beanstalkd.list_tubes_watched().unwrap(), vec![tube_name.to_string()]);
+}
+
+#[test]
+fn list_tubes_contains_used_tube() {
+    let tube_name = "list_tubes_contains_used_tube";
+    let mut beanstalkd = Beanstalkd::localhost().unwrap();
+    beanstalkd.tube(tube_name).unwrap();
+
+    let tubes = beanstalkd.list_tubes().unwrap();
+    assert!(tubes.contains(&"default".to_string()));
+    assert!(tubes.contains(&tube_name.to_string()));
+}
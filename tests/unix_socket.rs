@@ -0,0 +1,85 @@
+// Test connections over Unix domain sockets
+
+#![cfg(unix)]
+
+extern crate beanstalkd;
+
+use std::env;
+use std::fs;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::thread;
+
+use beanstalkd::Beanstalkd;
+
+mod common;
+use common::{replies, serve, Answer};
+
+/// Starts a server on a fresh socket file which serves one connection with `answer`
+fn fake_server(name: &str, mut answer: Answer) -> PathBuf {
+    let path = env::temp_dir().join(format!("beanstalkd-{}-{}.sock", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        serve(stream, &mut answer);
+    });
+    path
+}
+
+#[test]
+fn commands_over_unix_socket() {
+    let path = fake_server("commands",
+                           replies(&["USING unix\r\n",
+                                     "INSERTED 4\r\n",
+                                     "WATCHING 2\r\n",
+                                     "RESERVED 4 11\r\nhello\r\nunix\r\n",
+                                     "DELETED\r\n",
+                                     "OK 21\r\n---\n- default\n- unix\n\r\n"]));
+    let mut beanstalkd = Beanstalkd::connect_unix(&path).unwrap();
+
+    beanstalkd.tube("unix").unwrap();
+    assert_eq!(beanstalkd.put("hello\r\nunix", 0, 0, 60).unwrap(), 4);
+    assert_eq!(beanstalkd.watch("unix").unwrap(), 2);
+    assert_eq!(beanstalkd.reserve().unwrap(), (4, "hello\r\nunix".to_string()));
+    beanstalkd.delete(4).unwrap();
+    assert_eq!(beanstalkd.list_tubes().unwrap(), vec!["default".to_string(), "unix".to_string()]);
+
+    fs::remove_file(&path).unwrap();
+}
+
+/// Runs against a real server if `BEANSTALKD_SOCKET` names its socket file, e.g. of one started
+/// with `beanstalkd -l unix:/tmp/beanstalkd.sock`, and is skipped otherwise
+#[test]
+fn real_server_over_unix_socket() {
+    let path = match env::var_os("BEANSTALKD_SOCKET") {
+        Some(path) => path,
+        None => return,
+    };
+    let mut beanstalkd = Beanstalkd::connect_unix(path).unwrap();
+    beanstalkd.tube("unix").unwrap();
+    let id = beanstalkd.put("over a socket file", 0, 0, 60).unwrap();
+
+    assert_eq!(beanstalkd.peek(id).unwrap(), Some((id, "over a socket file".to_string())));
+    beanstalkd.delete(id).unwrap();
+}
+
+#[test]
+fn missing_socket_file() {
+    let path = env::temp_dir().join("beanstalkd-missing.sock");
+    assert!(Beanstalkd::connect_unix(path).is_err());
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_commands_over_unix_socket() {
+    use beanstalkd::AsyncBeanstalkd;
+
+    let path = fake_server("async-commands", replies(&["INSERTED 9\r\n", "FOUND 9 2\r\nhi\r\n"]));
+    let mut beanstalkd = AsyncBeanstalkd::connect_unix(&path).await.unwrap();
+
+    assert_eq!(beanstalkd.put("hi", 0, 0, 60).await.unwrap(), 9);
+    assert_eq!(beanstalkd.peek(9).await.unwrap(), Some((9, "hi".to_string())));
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,61 @@
+// Test connecting with URLs
+
+extern crate beanstalkd;
+
+use std::env;
+
+use beanstalkd::{Beanstalkd, BeanstalkdError, URL_VARIABLE};
+
+mod common;
+use common::{tcp_server, FakeServer};
+
+/// Starts a server which acknowledges `use`, `watch` and `ignore` and reports each command line
+fn fake_server() -> FakeServer {
+    tcp_server(vec![Box::new(|line| {
+        let reply = match line.split(' ').next().unwrap() {
+            "use" => format!("USING {}\r\n", &line[4..]),
+            "watch" | "ignore" => "WATCHING 1\r\n".to_string(),
+            _ => "UNKNOWN_COMMAND\r\n".to_string(),
+        };
+        Some(reply.into_bytes())
+    })])
+}
+
+#[test]
+fn from_url_selects_tubes() {
+    let server = fake_server();
+    let url = format!("beanstalk://127.0.0.1:{}/jobs?watch=a,b", server.port);
+    Beanstalkd::from_url(&url).unwrap();
+
+    let commands: Vec<String> = server.commands.iter().take(4).collect();
+    assert_eq!(commands, vec!["use jobs", "watch a", "watch b", "ignore default"]);
+}
+
+#[test]
+fn from_url_keeps_watching_default() {
+    let server = fake_server();
+    let url = format!("beanstalk://127.0.0.1:{}?watch=default,b", server.port);
+    let mut beanstalkd = Beanstalkd::from_url(&url).unwrap();
+    assert!(matches!(beanstalkd.stats(), Err(BeanstalkdError::UnknownCommand)));
+
+    let commands: Vec<String> = server.commands.iter().take(3).collect();
+    assert_eq!(commands, vec!["watch default", "watch b", "stats"]);
+}
+
+#[test]
+fn from_url_rejects_invalid_urls() {
+    assert!(matches!(Beanstalkd::from_url("localhost:11300"), Err(BeanstalkdError::InvalidUrl(_))));
+    #[cfg(not(feature = "tls"))]
+    assert!(matches!(Beanstalkd::from_url("beanstalks://localhost"),
+                     Err(BeanstalkdError::InvalidUrl(_))));
+}
+
+#[test]
+fn from_env_reads_url() {
+    let server = fake_server();
+    env::set_var(URL_VARIABLE, format!("beanstalk://127.0.0.1:{}/env", server.port));
+    Beanstalkd::from_env().unwrap();
+    env::remove_var(URL_VARIABLE);
+
+    assert_eq!(server.commands.recv().unwrap(), "use env");
+}